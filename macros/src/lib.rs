@@ -0,0 +1,253 @@
+//! Attribute form of `flashops::flash_algorithm!`, re-exported as
+//! `flashops::attr::flash_algorithm` with the `attribute` feature.
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// Keys accepted by `flash_algorithm!`, in the order the declarative macro expects them.
+/// `tests/attribute.rs` checks the list against the macro's main arm.
+const KEYS: &[&str] = &[
+    "flash_address",
+    "flash_size",
+    "regions",
+    "page_size",
+    "empty_value",
+    "sectors",
+    "device_name",
+    "device_type",
+    "program_timeout",
+    "erase_timeout",
+    "stack_size",
+    "ram_buffer_size",
+    "page_buffers",
+    "version",
+    "device_address",
+    "clock_range",
+    "init_retries",
+    "entry_section",
+    "device_section",
+    "pad_pages",
+    "protected_sectors",
+    "program_granularity",
+    "log_buffer",
+    "empty_pattern",
+    "data_region",
+    "sector_sentinel",
+    "transfer_buffer",
+    "write_chunk",
+    "allowed_ids",
+    "ecc_size",
+    "word_order",
+    "requires_cache_disable",
+    "buffer_alignment",
+];
+
+/// Generates the same entry points and descriptors as `flash_algorithm!` for the type of the
+/// annotated `impl FlashOps for ...` block.
+///
+/// Arguments are the `flash_algorithm!` keys written as `key = value` and may come in any
+/// order. Unknown, repeated or missing keys are reported on the offending argument.
+///
+/// The expansion invokes `::flashops::flash_algorithm!`. A crate that renames the dependency
+/// passes its path as `crate = path`, e.g. `crate = ::fops` for
+/// `fops = { package = "flashops", ... }`.
+///
+/// ```ignore
+/// #[flash_algorithm(
+///     flash_address = 0x0800_0000,
+///     flash_size = 0x10000,
+///     page_size = 0x400,
+///     empty_value = 0xFF,
+///     sectors = [{size: 0x400, address: 0x0}],
+/// )]
+/// impl FlashOps for Algo { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn flash_algorithm(args: TokenStream, item: TokenStream) -> TokenStream {
+    let expansion = algo_type(&item).and_then(|algo| Ok((algo, parse_args(args)?)));
+    let mut output = item;
+    match expansion {
+        Ok((algo, (krate, args))) => output.extend(invocation(krate, algo, args)),
+        Err((span, message)) => output.extend(compile_error(span, &message)),
+    }
+    output
+}
+
+type Error = (Span, String);
+
+/// Returns the tokens between `for` and the body of an `impl Trait for Type` block.
+fn algo_type(item: &TokenStream) -> Result<TokenStream, Error> {
+    let span = item
+        .clone()
+        .into_iter()
+        .next()
+        .map_or_else(Span::call_site, |token| token.span());
+    let error = || (span, "expected `impl FlashOps for Type`".to_string());
+    let mut tokens = item.clone().into_iter();
+    tokens.find(|t| is_ident(t, "impl")).ok_or_else(error)?;
+    tokens.find(|t| is_ident(t, "for")).ok_or_else(error)?;
+    let algo: TokenStream = tokens
+        .take_while(|t| !is_ident(t, "where") && !is_brace(t))
+        .collect();
+    if algo.is_empty() {
+        return Err(error());
+    }
+    Ok(algo)
+}
+
+/// `(key, value)` pairs in `KEYS` order.
+type Args = Vec<(Ident, TokenStream)>;
+
+/// Splits `key = value, ...` into the `crate` path, if given, and the remaining pairs sorted
+/// into `KEYS` order.
+fn parse_args(args: TokenStream) -> Result<(Option<TokenStream>, Args), Error> {
+    let mut krate: Option<TokenStream> = None;
+    let mut parsed: Vec<(usize, Ident, TokenStream)> = Vec::new();
+    let mut tokens = args.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let key = match token {
+            TokenTree::Ident(ident) => ident,
+            other => return Err((other.span(), "expected a `key = value` argument".into())),
+        };
+        let name = key.to_string();
+        let index = KEYS.iter().position(|k| *k == name);
+        if index.is_none() && name != "crate" {
+            return Err((key.span(), format!("unknown flash_algorithm key `{name}`")));
+        }
+        if parsed.iter().any(|(i, _, _)| Some(*i) == index) || (index.is_none() && krate.is_some())
+        {
+            return Err((key.span(), format!("`{name}` is given more than once")));
+        }
+        match tokens.next() {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '=' => {}
+            _ => return Err((key.span(), format!("expected `=` after `{name}`"))),
+        }
+        let mut value = Vec::new();
+        while let Some(token) = tokens.next_if(|t| !is_comma(t)) {
+            value.push(token);
+        }
+        tokens.next();
+        if value.is_empty() {
+            return Err((key.span(), format!("missing value for `{name}`")));
+        }
+        match index {
+            Some(index) => parsed.push((index, key, value.into_iter().collect())),
+            None => krate = Some(value.into_iter().collect()),
+        }
+    }
+    check_required(&parsed)?;
+    parsed.sort_by_key(|(index, _, _)| *index);
+    let args = parsed
+        .into_iter()
+        .map(|(_, key, value)| (key, value))
+        .collect();
+    Ok((krate, args))
+}
+
+fn check_required(parsed: &[(usize, Ident, TokenStream)]) -> Result<(), Error> {
+    let has = |name: &str| parsed.iter().any(|(_, key, _)| key.to_string() == name);
+    let (required, conflicting): (&[&str], &[&str]) = if has("regions") {
+        (
+            &["regions", "page_size", "empty_value"],
+            &["flash_address", "flash_size", "sectors"],
+        )
+    } else {
+        (
+            &[
+                "flash_address",
+                "flash_size",
+                "page_size",
+                "empty_value",
+                "sectors",
+            ],
+            &[],
+        )
+    };
+    if let Some((_, key, _)) = parsed
+        .iter()
+        .find(|(_, key, _)| conflicting.contains(&key.to_string().as_str()))
+    {
+        return Err((
+            key.span(),
+            format!("`{key}` cannot be combined with `regions`"),
+        ));
+    }
+    match required.iter().find(|name| !has(name)) {
+        Some(name) => Err((Span::call_site(), format!("missing required key `{name}`"))),
+        None => Ok(()),
+    }
+}
+
+fn is_comma(token: &TokenTree) -> bool {
+    matches!(token, TokenTree::Punct(punct) if punct.as_char() == ',')
+}
+
+fn is_ident(token: &TokenTree, name: &str) -> bool {
+    matches!(token, TokenTree::Ident(ident) if ident.to_string() == name)
+}
+
+fn is_brace(token: &TokenTree) -> bool {
+    matches!(token, TokenTree::Group(group) if group.delimiter() == Delimiter::Brace)
+}
+
+/// Builds `::flashops::flash_algorithm!(algo, { key: value, ... });`, with `krate` in place
+/// of `::flashops` when given.
+fn invocation(krate: Option<TokenStream>, algo: TokenStream, args: Args) -> TokenStream {
+    let mut body = Vec::new();
+    for (i, (key, value)) in args.into_iter().enumerate() {
+        if i > 0 {
+            body.push(TokenTree::Punct(Punct::new(',', Spacing::Alone)));
+        }
+        body.push(TokenTree::Ident(key));
+        body.push(TokenTree::Punct(Punct::new(':', Spacing::Alone)));
+        body.extend(value);
+    }
+    let mut inner: Vec<TokenTree> = algo.into_iter().collect();
+    inner.push(TokenTree::Punct(Punct::new(',', Spacing::Alone)));
+    inner.push(TokenTree::Group(Group::new(
+        Delimiter::Brace,
+        body.into_iter().collect(),
+    )));
+    let mut output = match krate {
+        Some(krate) => {
+            let mut output: Vec<TokenTree> = krate.into_iter().collect();
+            output.extend(path(&["flash_algorithm"], Span::call_site()));
+            output
+        }
+        None => path(&["flashops", "flash_algorithm"], Span::call_site()),
+    };
+    output.push(TokenTree::Punct(Punct::new('!', Spacing::Alone)));
+    output.push(TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        inner.into_iter().collect(),
+    )));
+    output.push(TokenTree::Punct(Punct::new(';', Spacing::Alone)));
+    output.into_iter().collect()
+}
+
+fn compile_error(span: Span, message: &str) -> TokenStream {
+    let mut output = path(&["core", "compile_error"], span);
+    output.push(TokenTree::Punct(Punct::new('!', Spacing::Alone)));
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    let mut group = Group::new(Delimiter::Brace, TokenTree::Literal(literal).into());
+    group.set_span(span);
+    output.push(TokenTree::Group(group));
+    output
+        .into_iter()
+        .map(|mut token| {
+            token.set_span(span);
+            token
+        })
+        .collect()
+}
+
+/// Builds an absolute path such as `::core::compile_error`.
+fn path(segments: &[&str], span: Span) -> Vec<TokenTree> {
+    let mut tokens = Vec::new();
+    for segment in segments {
+        tokens.push(TokenTree::Punct(Punct::new(':', Spacing::Joint)));
+        tokens.push(TokenTree::Punct(Punct::new(':', Spacing::Alone)));
+        tokens.push(TokenTree::Ident(Ident::new(segment, span)));
+    }
+    tokens
+}
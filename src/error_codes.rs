@@ -0,0 +1,56 @@
+//! Every error code reserved by the crate, as the values the generated entry points return.
+//!
+//! Algorithm-specific codes must stay outside [`RESERVED_RANGE`], which also covers codes not
+//! allocated yet, so that a later release can add one without colliding with them.
+
+use crate::{Error, ErrorCode};
+use core::ops::RangeInclusive;
+
+/// Codes reserved for the crate, off-limits to algorithm-specific errors.
+pub const RESERVED_RANGE: RangeInclusive<u32> = 0xFFFF_FF00..=0xFFFF_FFFF;
+
+pub const NOT_INITIALIZED: Error = ErrorCode::NotInitialized.error();
+pub const ERASE_FAILED: Error = ErrorCode::EraseFailed.error();
+pub const PROGRAM_FAILED: Error = ErrorCode::ProgramFailed.error();
+pub const VERIFY_MISMATCH: Error = ErrorCode::VerifyMismatch.error();
+pub const READ_FAILED: Error = ErrorCode::ReadFailed.error();
+pub const NOT_BLANK: Error = ErrorCode::NotBlank.error();
+pub const ADDRESS_OUT_OF_RANGE: Error = ErrorCode::AddressOutOfRange.error();
+pub const UNALIGNED_PROGRAM: Error = ErrorCode::UnalignedProgram.error();
+pub const INVALID_OPERATION: Error = ErrorCode::InvalidOperation.error();
+pub const BUSY: Error = ErrorCode::Busy.error();
+pub const CLOCK_OUT_OF_RANGE: Error = ErrorCode::ClockOutOfRange.error();
+pub const INVALID_ARGUMENT: Error = ErrorCode::InvalidArgument.error();
+pub const SECTOR_PROTECTED: Error = ErrorCode::SectorProtected.error();
+pub const UNALIGNED_ERASE: Error = ErrorCode::UnalignedErase.error();
+pub const UNSUPPORTED_OPERATION: Error = ErrorCode::UnsupportedOperation.error();
+pub const WRONG_DEVICE: Error = ErrorCode::WrongDevice.error();
+pub const MISALIGNED_BUFFER: Error = ErrorCode::MisalignedBuffer.error();
+pub const TIMEOUT: Error = ErrorCode::Timeout.error();
+
+/// Every code above, in increasing order.
+pub const ALL: [Error; ErrorCode::ALL.len()] = {
+    let mut all = [NOT_INITIALIZED; ErrorCode::ALL.len()];
+    let mut i = 0;
+    while i < all.len() {
+        all[i] = ErrorCode::ALL[i].error();
+        i += 1;
+    }
+    all
+};
+
+/// Returns whether `code` lies in [`RESERVED_RANGE`].
+pub const fn is_reserved(code: u32) -> bool {
+    code >= *RESERVED_RANGE.start()
+}
+
+const _: () = {
+    let mut i = 0;
+    while i < ALL.len() {
+        assert!(
+            is_reserved(ALL[i].get()),
+            "error code outside RESERVED_RANGE"
+        );
+        i += 1;
+    }
+};
@@ -1,186 +1,4192 @@
 #![no_std]
-#![no_main]
 #![macro_use]
 
-#[cfg(all(not(test), feature = "panic-handler"))]
-#[panic_handler]
-fn handle_panic(_info: &core::panic::PanicInfo) -> ! {
-    unsafe {
-        core::arch::asm!("udf #0");
-        core::hint::unreachable_unchecked();
+// `no-checks` strips the checks these features add, so enabling both would silently do nothing.
+#[cfg(all(feature = "no-checks", feature = "bounds-check"))]
+compile_error!("features `no-checks` and `bounds-check` are mutually exclusive: `no-checks` removes the bounds checks");
+
+#[cfg(all(feature = "no-checks", feature = "strict-erase"))]
+compile_error!("features `no-checks` and `strict-erase` are mutually exclusive: `no-checks` removes the erase alignment check");
+
+#[cfg(all(feature = "no-checks", feature = "strict-program"))]
+compile_error!("features `no-checks` and `strict-program` are mutually exclusive: `no-checks` removes the program alignment check");
+
+// Without `read` there is no default `verify` to fall back on, which is rarely what was meant.
+#[cfg(all(
+    feature = "verify",
+    not(feature = "read"),
+    not(feature = "custom-verify")
+))]
+compile_error!("feature `verify` needs `read` for its default implementation: enable `read`, or `custom-verify` to implement `verify` without it");
+
+#[cfg(all(feature = "no-unsupported-stubs", feature = "full-symbol-table"))]
+compile_error!("features `no-unsupported-stubs` and `full-symbol-table` are mutually exclusive: `full-symbol-table` emits every stub that `no-unsupported-stubs` removes");
+
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "std")]
+mod sim;
+
+#[cfg(feature = "std")]
+pub use sim::FlashSim;
+
+#[cfg(feature = "std")]
+mod target;
+
+#[cfg(feature = "std")]
+pub use target::flash_properties_yaml;
+
+#[cfg(feature = "panic-handler")]
+mod panic;
+
+/// Serializes the host wrappers generated by [`flash_algorithm!`], since the algorithm state
+/// they reach is a single global and the test harness runs tests in parallel.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub fn host_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(feature = "log")]
+mod log;
+
+#[cfg(feature = "log")]
+pub use log::{FlashLog, LogBuffer};
+
+pub mod error_codes;
+
+#[cfg(feature = "mmio")]
+mod mmio;
+
+#[cfg(feature = "mmio")]
+pub use mmio::{Bus, Mmio, MmioController, Word};
+
+/// Attribute form of [`flash_algorithm!`], placed on the `impl FlashOps` block and taking the
+/// same keys as `key = value` arguments in any order.
+#[cfg(feature = "attribute")]
+pub mod attr {
+    pub use flashops_macros::flash_algorithm;
+}
+
+pub const ERASE: u32 = 1;
+pub const PROGRAM: u32 = 2;
+pub const VERIFY: u32 = 3;
+
+/// Bit of the `reg` argument of [`FlashOps::write_config`] requesting a volatile write.
+pub const CONFIG_VOLATILE: u32 = 1 << 31;
+
+/// Error returned by the algorithm, a [`ErrorCode`] or an algorithm-specific code. With the
+/// `defmt` feature it formats through `defmt`'s own implementation for `NonZeroU32`.
+pub type Error = core::num::NonZeroU32;
+
+/// First word of the `FlashAlgorithmInfo` symbol: the bytes `FOPS` read as a little-endian
+/// `u32`.
+pub const ALGORITHM_INFO_MAGIC: u32 = 0x5350_4F46;
+/// Layout version of the `FlashAlgorithmInfo` symbol, bumped whenever fields change.
+pub const ALGORITHM_INFO_VERSION: u32 = 4;
+
+/// Bit of the `flags` word of `FlashAlgorithmInfo` set by `requires_cache_disable: true`: the
+/// host must disable the flash cache and prefetch buffer before running the algorithm.
+pub const ALGORITHM_FLAG_CACHE_DISABLE: u32 = 1 << 0;
+
+/// `vers` word of the `FlashDeviceInfoV2` descriptor emitted with the `descriptor-v2` feature,
+/// which tells hosts it carries the extended fields.
+pub const FLASH_DEVICE_V2_VERS: u16 = 0x0200;
+
+/// Well-known error codes, allocated from the top of the `u32` range so they
+/// stay clear of small algorithm-specific codes. [`error_codes`] lists them as [`Error`]
+/// values together with the whole reserved range, and [`flash_algorithm!`] describes when its
+/// entry points return them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u32)]
+pub enum ErrorCode {
+    NotInitialized = 0xFFFF_FF01,
+    EraseFailed = 0xFFFF_FF02,
+    ProgramFailed = 0xFFFF_FF03,
+    VerifyMismatch = 0xFFFF_FF04,
+    ReadFailed = 0xFFFF_FF05,
+    NotBlank = 0xFFFF_FF06,
+    AddressOutOfRange = 0xFFFF_FF07,
+    UnalignedProgram = 0xFFFF_FF08,
+    InvalidOperation = 0xFFFF_FF09,
+    Busy = 0xFFFF_FF0A,
+    ClockOutOfRange = 0xFFFF_FF0B,
+    InvalidArgument = 0xFFFF_FF0C,
+    SectorProtected = 0xFFFF_FF0D,
+    UnalignedErase = 0xFFFF_FF0E,
+    UnsupportedOperation = 0xFFFF_FF0F,
+    WrongDevice = 0xFFFF_FF10,
+    MisalignedBuffer = 0xFFFF_FF11,
+    Timeout = 0xFFFF_FF12,
+}
+
+impl ErrorCode {
+    pub const fn code(self) -> u32 {
+        self as u32
+    }
+
+    pub const fn error(self) -> Error {
+        match Error::new(self.code()) {
+            Some(error) => error,
+            None => unreachable!(),
+        }
+    }
+}
+
+impl ErrorCode {
+    /// Every variant, in increasing order; [`error_codes::ALL`] is built from it.
+    pub(crate) const ALL: [ErrorCode; 18] = [
+        ErrorCode::NotInitialized,
+        ErrorCode::EraseFailed,
+        ErrorCode::ProgramFailed,
+        ErrorCode::VerifyMismatch,
+        ErrorCode::ReadFailed,
+        ErrorCode::NotBlank,
+        ErrorCode::AddressOutOfRange,
+        ErrorCode::UnalignedProgram,
+        ErrorCode::InvalidOperation,
+        ErrorCode::Busy,
+        ErrorCode::ClockOutOfRange,
+        ErrorCode::InvalidArgument,
+        ErrorCode::SectorProtected,
+        ErrorCode::UnalignedErase,
+        ErrorCode::UnsupportedOperation,
+        ErrorCode::WrongDevice,
+        ErrorCode::MisalignedBuffer,
+        ErrorCode::Timeout,
+    ];
+
+    const fn description(self) -> &'static str {
+        match self {
+            ErrorCode::NotInitialized => "algorithm not initialized",
+            ErrorCode::EraseFailed => "erase failed",
+            ErrorCode::ProgramFailed => "program failed",
+            ErrorCode::VerifyMismatch => "verify mismatch",
+            ErrorCode::ReadFailed => "read failed",
+            ErrorCode::NotBlank => "region not blank",
+            ErrorCode::AddressOutOfRange => "address out of range",
+            ErrorCode::UnalignedProgram => "program request not page aligned",
+            ErrorCode::InvalidOperation => "invalid operation code",
+            ErrorCode::Busy => "algorithm busy",
+            ErrorCode::ClockOutOfRange => "clock frequency out of range",
+            ErrorCode::InvalidArgument => "invalid argument",
+            ErrorCode::SectorProtected => "sector protected",
+            ErrorCode::UnalignedErase => "erase address not a sector base",
+            ErrorCode::UnsupportedOperation => "operation not supported",
+            ErrorCode::WrongDevice => "connected device not allowed",
+            ErrorCode::MisalignedBuffer => "data buffer misaligned",
+            ErrorCode::Timeout => "controller still busy after the poll limit",
+        }
+    }
+}
+
+impl core::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// Returns the description of one of the crate's reserved [`ErrorCode`]s, as printed by its
+/// `Display` implementation, for host tooling that only has the raw value returned by an entry
+/// point. Algorithm-specific codes return `None`.
+#[cfg(feature = "error-desc")]
+pub fn describe(code: u32) -> Option<&'static str> {
+    ErrorCode::ALL
+        .iter()
+        .find(|error| error.code() == code)
+        .map(|error| error.description())
+}
+
+impl From<ErrorCode> for Error {
+    fn from(code: ErrorCode) -> Self {
+        code.error()
+    }
+}
+
+pub trait FlashOps {
+    fn create(address: u32, clock: u32, operation: Operation) -> Result<Self, Error>
+    where
+        Self: Sized;
+
+    /// Called between two `create` attempts when `init_retries` allows more than one, with the
+    /// number of the attempt that just failed. The default returns immediately.
+    fn retry_delay(_attempt: u32)
+    where
+        Self: Sized,
+    {
+    }
+
+    /// Called between iterations of the crate-provided default loops: after each page in
+    /// `program_pages`, each sector in `erase_chip` and `erase_range`, and each chunk read back
+    /// by the `verify` and `is_sector_erased` defaults. The default does nothing; override it
+    /// to kick an always-on watchdog. Loops inside your own methods must feed it themselves.
+    fn feed_watchdog(&mut self) {}
+
+    /// Receives a progress message from the crate-provided default loops, which log their name
+    /// once when they start. The default discards it; forward it to [`FlashLog::write`] on a
+    /// [`LogBuffer`], typically the one emitted by the `log_buffer` key, to watch progress from
+    /// the host.
+    #[cfg(feature = "log")]
+    fn log(&mut self, _message: &str) {}
+
+    /// Performs one bounded piece of a chip erase, for cooperative loaders that need the
+    /// algorithm to return regularly so they can poll and feed a watchdog.
+    ///
+    /// `step` is `0` on the first call of an erase and grows by one on each following call. The
+    /// `erase_chip_step` entry point keeps it, calling again with the next step after
+    /// [`StepResult::InProgress`] and starting over at `0` after [`StepResult::Done`], after an
+    /// error, and after `initialize`. The default erases the `step`-th sector of the table with
+    /// `erase_sector`, skipping the gaps between banks, and returns `Done` for the last one, so
+    /// each call lasts at most one sector erase.
+    #[cfg(feature = "erase-step")]
+    fn erase_chip_step(&mut self, step: u32) -> Result<StepResult, Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        let mut remaining = step;
+        for (i, &(size, start)) in Self::SECTORS.iter().enumerate() {
+            let count = entry_sector_count::<Self>(i);
+            if remaining < count {
+                self.erase_sector(Self::FLASH_ADDRESS + start + remaining * size)?;
+                advance_erase_progress::<Self>();
+                return Ok(if i + 1 == Self::SECTORS.len() && remaining + 1 == count {
+                    StepResult::Done
+                } else {
+                    StepResult::InProgress
+                });
+            }
+            remaining -= count;
+        }
+        Err(ErrorCode::InvalidArgument.into())
+    }
+
+    /// Erases the whole flash.
+    ///
+    /// The default erases every sector in the table with `erase_sector`, skipping the gaps
+    /// between banks; override it when the controller has a mass-erase command.
+    #[cfg(feature = "erase-chip")]
+    fn erase_chip(&mut self) -> Result<(), Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        #[cfg(feature = "log")]
+        self.log("erase_chip");
+        let mut address = Self::FLASH_ADDRESS as u64;
+        while let Some((base, size)) = sector_from::<Self>(address) {
+            self.erase_sector(base)?;
+            advance_erase_progress::<Self>();
+            self.feed_watchdog();
+            address = base as u64 + size as u64;
+        }
+        Ok(())
+    }
+
+    fn erase_sector(&mut self, address: u32) -> Result<(), Error>;
+    fn program_page(&mut self, address: u32, data: &[u8]) -> Result<(), Error>;
+
+    /// Programs a span of whole pages in one call.
+    ///
+    /// `address` must be page aligned and `data.len()` a multiple of the page size, otherwise
+    /// [`ErrorCode::UnalignedProgram`] is returned. The default calls `program_page` once per
+    /// page; override it to keep the controller's write buffer fed across pages.
+    #[cfg(feature = "program-pages")]
+    fn program_pages(&mut self, address: u32, data: &[u8]) -> Result<(), Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        #[cfg(feature = "log")]
+        self.log("program_pages");
+        Self::check_page_span(address, data.len())?;
+        let pages = pages_in(address, data.len() as u32, Self::PAGE_SIZE);
+        for (base, page) in pages.zip(data.chunks(Self::PAGE_SIZE as usize)) {
+            self.program_page(base, page)?;
+            self.feed_watchdog();
+        }
+        Ok(())
+    }
+
+    /// Erases the page of the `data_region` containing `address`, called by the `erase_sector`
+    /// entry point instead of [`FlashOps::erase_sector`] for addresses inside that region. The
+    /// default forwards to `erase_sector`.
+    fn erase_data(&mut self, address: u32) -> Result<(), Error> {
+        self.erase_sector(address)
+    }
+
+    /// Programs `data` within a single page of the `data_region`, called by the `program_page`
+    /// entry point instead of [`FlashOps::program_page`] for ranges inside that region, after
+    /// checking them against its own `page_size` and `program_granularity`. The default
+    /// forwards to `program_page`.
+    fn program_data(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        self.program_page(address, data)
+    }
+
+    /// Computes the ECC bytes stored in the spare area for `data`, in the layout
+    /// [`FlashOps::program_page_ecc`] writes. `out` holds `ECC_SIZE` bytes.
+    ///
+    /// The default fills `out` with the 3-byte [`hamming_ecc`] of each 256-byte block of
+    /// `data` in turn, the scheme used by small-page NAND, and leaves bytes past the last
+    /// block untouched.
+    #[cfg(feature = "ecc")]
+    fn compute_ecc(&self, data: &[u8], out: &mut [u8]) {
+        for (block, code) in data.chunks(256).zip(out.chunks_mut(3)) {
+            let ecc = hamming_ecc(block);
+            code.copy_from_slice(&ecc[..code.len()]);
+        }
+    }
+
+    /// Programs `data` to the main area at `address` and `ecc` to the spare area of the same
+    /// page, called by the `program_page` entry point instead of [`FlashOps::program_page`]
+    /// with the `ecc` feature.
+    #[cfg(feature = "ecc")]
+    fn program_page_ecc(&mut self, address: u32, data: &[u8], ecc: &[u8]) -> Result<(), Error>;
+
+    /// Programs from the start of `data` like [`FlashOps::program_page`] and returns how many
+    /// bytes it consumed, which the matching entry point writes to its `out` parameter.
+    ///
+    /// A count smaller than `data.len()` tells the host to continue at `address + count`, for
+    /// instance after stopping at a page boundary; skipped bytes that are already blank count
+    /// as consumed. The count must not exceed `data.len()`. The default programs the whole
+    /// buffer and returns `data.len()`.
+    #[cfg(feature = "program-counted")]
+    fn program_page_counted(&mut self, address: u32, data: &[u8]) -> Result<u32, Error> {
+        self.program_page(address, data)?;
+        Ok(data.len() as u32)
+    }
+
+    /// Writes one chunk of at most [`FlashGeometry::WRITE_CHUNK`] bytes, for algorithms whose
+    /// `program_page` forwards to [`program_chunked`]. The default returns
+    /// [`ErrorCode::UnsupportedOperation`].
+    fn program_chunk(&mut self, _address: u32, _data: &[u8]) -> Result<(), Error> {
+        Err(ErrorCode::UnsupportedOperation.into())
+    }
+
+    /// Called by [`program_chunked`] after each chunk, to poll the controller until it can
+    /// take the next one. The default returns immediately.
+    fn wait_chunk(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Programs `data` like [`FlashOps::program_page`], then checks it back against the same
+    /// buffer, so the host gets a verified page from a single call.
+    ///
+    /// A failed write returns the error of `program_page`, typically
+    /// [`ErrorCode::ProgramFailed`], while data that was written but reads back differently
+    /// returns [`ErrorCode::VerifyMismatch`]. The default chains `program_page` and
+    /// [`FlashOps::verify`]; override it when the controller verifies during the write.
+    #[cfg(feature = "program-verify")]
+    fn program_and_verify(&mut self, address: u32, data: &[u8]) -> Result<(), Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        self.program_page(address, data)?;
+        self.verify(address, data.len() as u32, Some(data))
+    }
+
+    /// Called once per `initialize`, right before the first erase or program request reaches
+    /// the algorithm, to write the controller's unlock sequence. When it fails the request
+    /// returns its error and the next one calls it again. The default does nothing.
+    fn unlock(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called by `deinitialize`, before [`FlashOps::uninit`], when [`FlashOps::unlock`] has
+    /// succeeded, to lock the controller again. It runs even if the operations in between
+    /// failed. The default does nothing.
+    fn lock(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called by the `finalize` entry point once all programming is done, to commit
+    /// configuration or reset the part so the new contents take effect. The default does
+    /// nothing.
+    ///
+    /// Hosts calling `finalize` do so as `initialize`, `program_page`..., `finalize`, then
+    /// `deinitialize`; unlike [`FlashOps::uninit`] it is not run on every teardown.
+    fn finalize(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called by `deinitialize` with the operation passed to `create`, before the instance is
+    /// dropped. The instance is dropped even if this fails.
+    fn uninit(&mut self, _operation: Operation) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called by `deinitialize` after [`FlashOps::uninit`], right before the instance is
+    /// dropped, to power the controller down or disable its clocks. An error is reported to the
+    /// host but the instance is dropped regardless. The default does nothing.
+    fn deinit(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Compares the region against `data`, or checks that it is blank when `data` is `None`.
+    ///
+    /// The generated `verify` entry point passes `Some` with exactly `size` bytes when the host
+    /// supplies a buffer, and `None` when it passes a null pointer to request a blank check.
+    /// Either way, return [`ErrorCode::VerifyMismatch`] at the first byte that differs. In
+    /// the blank check a byte is expected to hold its erased value, which is
+    /// [`FlashGeometry::empty_byte`] and so follows `empty_pattern` when one is declared.
+    /// Without `read` this method is required, which the `custom-verify` feature confirms.
+    #[cfg(all(feature = "verify", not(feature = "read")))]
+    fn verify(&mut self, address: u32, size: u32, data: Option<&[u8]>) -> Result<(), Error>;
+
+    /// Compares the region against `data`, or checks that it is blank when `data` is `None`.
+    ///
+    /// The generated `verify` entry point passes `Some` with exactly `size` bytes when the host
+    /// supplies a buffer, and `None` when it passes a null pointer to request a blank check.
+    /// With both the `verify` and `read` features enabled this default reads the region back
+    /// through [`FlashOps::read`] in small chunks and fails with [`ErrorCode::VerifyMismatch`]
+    /// at the first differing byte, comparing against [`FlashGeometry::empty_byte`] for a
+    /// blank check. Override it when the controller can verify in hardware, keeping the same
+    /// meaning for `None`.
+    #[cfg(all(feature = "verify", feature = "read"))]
+    fn verify(&mut self, address: u32, size: u32, data: Option<&[u8]>) -> Result<(), Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        #[cfg(feature = "log")]
+        self.log("verify");
+        let mut buffer = [0u8; 64];
+        let mut offset = 0;
+        while offset < size {
+            let len = core::cmp::min(size - offset, buffer.len() as u32) as usize;
+            let chunk = &mut buffer[..len];
+            self.read(address + offset, chunk)?;
+            let start = offset as usize;
+            let matches = match data {
+                Some(data) => data.get(start..start + len) == Some(&*chunk),
+                None => chunk
+                    .iter()
+                    .enumerate()
+                    .all(|(i, &byte)| byte == Self::empty_byte(address + offset + i as u32)),
+            };
+            if !matches {
+                return Err(ErrorCode::VerifyMismatch.into());
+            }
+            self.feed_watchdog();
+            offset += len as u32;
+        }
+        Ok(())
+    }
+
+    /// Reads `data.len()` bytes starting at `address`, for hosts that cannot read the flash
+    /// over the bus, such as external QSPI parts that are not memory-mapped.
+    ///
+    /// [`flash_algorithm!`] exports it as the `read(addr, size, data)` entry point, defined as
+    /// the Rust function `flash_read` so that the name does not clash with the C library's
+    /// `read` in host builds.
+    #[cfg(feature = "read")]
+    fn read(&mut self, address: u32, data: &mut [u8]) -> Result<(), Error>;
+
+    /// Returns whether the sector containing `address` holds only erased contents.
+    ///
+    /// The default reads the sector back through [`FlashOps::read`]; override it when the
+    /// controller has a blank-check facility.
+    #[cfg(feature = "read")]
+    fn is_sector_erased(&mut self, address: u32) -> Result<bool, Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        #[cfg(feature = "log")]
+        self.log("is_sector_erased");
+        let (base, size) = sector_at::<Self>(address).ok_or(ErrorCode::AddressOutOfRange)?;
+        let mut buffer = [0u8; 64];
+        let mut offset = 0;
+        while offset < size {
+            let len = core::cmp::min(size - offset, buffer.len() as u32) as usize;
+            let chunk = &mut buffer[..len];
+            self.read(base + offset, chunk)?;
+            if chunk
+                .iter()
+                .enumerate()
+                .any(|(i, &byte)| byte != Self::empty_byte(base + offset + i as u32))
+            {
+                return Ok(false);
+            }
+            self.feed_watchdog();
+            offset += len as u32;
+        }
+        Ok(true)
+    }
+
+    /// Estimates in milliseconds how long `op` takes on `size` bytes from the start of the
+    /// flash, so the host can scale a progress bar. It is an estimate only, not a timeout.
+    ///
+    /// The default multiplies the number of pages by `PROGRAM_TIMEOUT` for
+    /// [`Operation::Program`] and the number of sectors covered by `ERASE_TIMEOUT` for
+    /// [`Operation::Erase`], so it errs on the slow side, and returns `0` for
+    /// [`Operation::Verify`], for which no timing is declared. Override it with datasheet
+    /// timings for a closer figure.
+    #[cfg(feature = "estimate")]
+    fn estimated_duration(&self, op: Operation, size: u32) -> u32
+    where
+        Self: FlashGeometry + Sized,
+    {
+        match op {
+            Operation::Program => size
+                .div_ceil(Self::PAGE_SIZE)
+                .saturating_mul(Self::PROGRAM_TIMEOUT),
+            Operation::Erase => {
+                let end =
+                    Self::FLASH_ADDRESS as u64 + core::cmp::min(size, Self::FLASH_SIZE) as u64;
+                let mut address = Self::FLASH_ADDRESS as u64;
+                let mut sectors: u32 = 0;
+                while let Some((base, sector_size)) = sector_from::<Self>(address) {
+                    if base as u64 >= end {
+                        break;
+                    }
+                    sectors += 1;
+                    address = base as u64 + sector_size as u64;
+                }
+                sectors.saturating_mul(Self::ERASE_TIMEOUT)
+            }
+            Operation::Verify => 0,
+        }
+    }
+
+    /// Erases the sector containing `address` unless it is already blank, returning whether
+    /// an erase took place, to spare the flash an erase cycle when reprogramming similar
+    /// images. The matching entry point writes the result to its `erased` parameter as `1` or
+    /// `0`. The default combines [`FlashOps::is_sector_erased`] and [`FlashOps::erase_sector`].
+    #[cfg(feature = "erase-if-needed")]
+    fn erase_if_needed(&mut self, address: u32) -> Result<bool, Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        if self.is_sector_erased(address)? {
+            return Ok(false);
+        }
+        self.erase_sector(address)?;
+        Ok(true)
+    }
+
+    /// Returns the erase granularity at `address`, for parts that report their geometry at
+    /// runtime, such as SPI NOR flash that may or may not support 4 KiB sector erase. Called
+    /// by the `sector_size` entry point once `initialize` has run, so it can use what `create`
+    /// read from the part.
+    ///
+    /// The default answers from the static geometry: the `data_region` page size for addresses
+    /// inside that region, otherwise the size of the sector table entry covering `address`, or
+    /// [`ErrorCode::AddressOutOfRange`] outside the flash.
+    #[cfg(feature = "sector-size")]
+    fn sector_size_at(&mut self, address: u32) -> Result<u32, Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        if let Some(region) = Self::DATA_REGION {
+            if region.contains(address, 1) {
+                return Ok(region.page_size);
+            }
+        }
+        sector_at::<Self>(address)
+            .map(|(_, size)| size)
+            .ok_or_else(|| ErrorCode::AddressOutOfRange.into())
+    }
+
+    /// Returns `Ok(())` when every byte in the region equals the device's empty value.
+    #[cfg(feature = "blank-check")]
+    fn blank_check(&mut self, address: u32, size: u32) -> Result<(), Error>;
+
+    /// Computes [`crc32`] over the region, so the host can compare a single value instead of
+    /// reading the data back.
+    #[cfg(feature = "checksum")]
+    fn checksum(&mut self, address: u32, size: u32) -> Result<u32, Error>;
+
+    /// Identifies the connected part so the host can check it before erasing anything, typically
+    /// right after `initialize` with [`Operation::Erase`].
+    ///
+    /// For SPI NOR flash return the three bytes of the JEDEC `0x9F` command: manufacturer ID in
+    /// bits 23..16, memory type in bits 15..8 and capacity in bits 7..0.
+    #[cfg(feature = "device-id")]
+    fn read_device_id(&mut self) -> Result<u32, Error>;
+
+    /// Writes `value` to a status or configuration register, such as the quad-enable bit of
+    /// an SPI NOR status register, so the host can set the part up before bulk programming
+    /// without going through `program_page`.
+    ///
+    /// Registers are device-specific. Bits 30..0 of `reg` select the register, conventionally
+    /// the command that writes it, such as `0x01` or `0x31` for SPI NOR status registers.
+    /// Bit 31, [`CONFIG_VOLATILE`], asks for a volatile write that is lost on reset, such as
+    /// one preceded by the `0x50` write-enable for volatile status register; without it the
+    /// write is non-volatile. Return [`ErrorCode::InvalidArgument`] for a register the part
+    /// does not have, and [`ErrorCode::UnsupportedOperation`] for a volatile write it cannot do.
+    #[cfg(feature = "write-config")]
+    fn write_config(&mut self, reg: u32, value: u32) -> Result<(), Error>;
+
+    /// Verifies the region against a [`crc32`] computed by the host, so the image itself does
+    /// not have to be transferred. The default compares against [`FlashOps::checksum`] and
+    /// fails with [`ErrorCode::VerifyMismatch`].
+    #[cfg(feature = "verify-crc")]
+    fn verify_crc(&mut self, address: u32, size: u32, expected: u32) -> Result<(), Error> {
+        if self.checksum(address, size)? == expected {
+            Ok(())
+        } else {
+            Err(ErrorCode::VerifyMismatch.into())
+        }
+    }
+
+    /// Verifies consecutive pages against CRCs precomputed by the host, page by page, so a large
+    /// image can be checked without transferring it and a failure is pinned to one page.
+    ///
+    /// `page_crcs[i]` is the [`crc32`] of the `PAGE_SIZE` bytes at `address + i * PAGE_SIZE`,
+    /// including any unprogrammed tail of the page as read back. The default walks the pages
+    /// in order through [`FlashOps::checksum`] and stops at the first that fails, writing its
+    /// index to `failed_page` and returning [`ErrorCode::VerifyMismatch`], or the checksum
+    /// error for that page. `failed_page` is left untouched on success.
+    #[cfg(feature = "verify-pages")]
+    fn verify_pages(
+        &mut self,
+        address: u32,
+        page_crcs: &[u32],
+        failed_page: &mut u32,
+    ) -> Result<(), Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        let mut page = address;
+        for (index, &expected) in page_crcs.iter().enumerate() {
+            let result = match self.checksum(page, Self::PAGE_SIZE) {
+                Ok(crc) if crc == expected => Ok(()),
+                Ok(_) => Err(ErrorCode::VerifyMismatch.into()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                *failed_page = index as u32;
+                return Err(e);
+            }
+            self.feed_watchdog();
+            page = page.wrapping_add(Self::PAGE_SIZE);
+        }
+        Ok(())
+    }
+
+    /// Erases every sector overlapping `address..address + length`.
+    ///
+    /// The default walks the sector table one `erase_sector` call at a time; override it when
+    /// the controller can erase a range in bulk.
+    #[cfg(feature = "erase-range")]
+    fn erase_range(&mut self, address: u32, length: u32) -> Result<(), Error>
+    where
+        Self: FlashGeometry + Sized,
+    {
+        #[cfg(feature = "log")]
+        self.log("erase_range");
+        let end = address
+            .checked_add(length)
+            .ok_or(ErrorCode::AddressOutOfRange)?;
+        let mut address = address;
+        while address < end {
+            let (base, size) = sector_at::<Self>(address).ok_or(ErrorCode::AddressOutOfRange)?;
+            self.erase_sector(base)?;
+            advance_erase_progress::<Self>();
+            self.feed_watchdog();
+            match base.checked_add(size) {
+                Some(next) => address = next,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Standard CRC-32 (IEEE 802.3, as used by zlib and Ethernet): reflected polynomial
+/// `0xEDB88320`, initial value and final XOR `0xFFFFFFFF`.
+///
+/// `crc` is the result of a previous call, or `0` to start, so data can be fed in chunks:
+/// `crc32(crc32(0, a), b) == crc32(0, ab)`.
+pub const fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    let mut i = 0;
+    while i < data.len() {
+        crc ^= data[i] as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        i += 1;
+    }
+    !crc
+}
+
+/// Hamming code of a block of up to 256 bytes, as used for NAND spare areas: it corrects a
+/// single flipped bit in the block and detects two. Bytes past the first 256 are ignored.
+///
+/// Bytes 0 and 1 hold the line parities, bit `2k` covering the bytes whose index has bit `k`
+/// clear and bit `2k + 1` those whose index has it set, for index bits 0..3 and 4..7. Byte 2
+/// holds the column parities in bits 7..2, covering bit positions `0xF0`, `0x0F`, `0xCC`,
+/// `0x33`, `0xAA` and `0x55` of every byte, with bits 1..0 set. Every parity bit is stored
+/// inverted, so an erased block of `0xFF` bytes, like one of zeros, has the code
+/// `[0xFF, 0xFF, 0xFF]`.
+pub const fn hamming_ecc(block: &[u8]) -> [u8; 3] {
+    let mut set: u8 = 0;
+    let mut clear: u8 = 0;
+    let mut columns: u8 = 0;
+    let mut i = 0;
+    while i < block.len() && i < 256 {
+        if block[i].count_ones() & 1 != 0 {
+            set ^= i as u8;
+            clear ^= !(i as u8);
+        }
+        columns ^= block[i];
+        i += 1;
+    }
+    let mut line: u16 = 0;
+    let mut bit = 0;
+    while bit < 8 {
+        line |= (((clear >> bit) & 1) as u16) << (2 * bit);
+        line |= (((set >> bit) & 1) as u16) << (2 * bit + 1);
+        bit += 1;
+    }
+    let masks = [0xF0u8, 0x0F, 0xCC, 0x33, 0xAA, 0x55];
+    let mut column: u8 = 0;
+    let mut j = 0;
+    while j < masks.len() {
+        column |= (((columns & masks[j]).count_ones() & 1) as u8) << (7 - j);
+        j += 1;
+    }
+    [!(line as u8), !((line >> 8) as u8), !column]
+}
+
+/// [`crc32`] over the `FlashDevice` fields tooling matches against its target database, as
+/// emitted by [`flash_algorithm!`] in `DESCRIPTOR_CRC`.
+///
+/// The hashed bytes are little-endian `u32` words: `dev_addr`, `device_size`, `page_size`,
+/// then the `size` and `address` of each entry of `sectors`, without the terminator.
+pub const fn descriptor_crc(
+    address: u32,
+    size: u32,
+    page_size: u32,
+    sectors: &[(u32, u32)],
+) -> u32 {
+    let mut crc = crc32(0, &address.to_le_bytes());
+    crc = crc32(crc, &size.to_le_bytes());
+    crc = crc32(crc, &page_size.to_le_bytes());
+    let mut i = 0;
+    while i < sectors.len() {
+        crc = crc32(crc, &sectors[i].0.to_le_bytes());
+        crc = crc32(crc, &sectors[i].1.to_le_bytes());
+        i += 1;
+    }
+    crc
+}
+
+/// Secondary flash region with its own programming semantics, such as the data flash some
+/// parts use for EEPROM emulation, as declared by the `data_region` key of [`flash_algorithm!`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DataRegion {
+    /// Absolute address of the region.
+    pub address: u32,
+    pub size: u32,
+    /// Size of the unit erased by `erase_sector` and bounding a single `program_page`.
+    pub page_size: u32,
+    /// Smallest unit in bytes that the region programs.
+    pub program_granularity: u32,
+}
+
+impl DataRegion {
+    /// Returns whether `address..address + size` lies entirely within the region.
+    pub const fn contains(&self, address: u32, size: u32) -> bool {
+        address >= self.address && (address - self.address) as u64 + size as u64 <= self.size as u64
+    }
+}
+
+/// Flash geometry declared through `flash_algorithm!`, which implements this trait for the
+/// algorithm type. The provided methods let generic code validate addresses against it.
+pub trait FlashGeometry {
+    const FLASH_ADDRESS: u32;
+    const FLASH_SIZE: u32;
+    const PAGE_SIZE: u32;
+    /// Value of an erased byte, as declared by `empty_value`.
+    const EMPTY_VALUE: u8;
+    /// Page program timeout in milliseconds.
+    const PROGRAM_TIMEOUT: u32;
+    /// Sector erase timeout in milliseconds.
+    const ERASE_TIMEOUT: u32;
+    /// `(size, address)` sector entries, addresses relative to `FLASH_ADDRESS`.
+    const SECTORS: &'static [(u32, u32)];
+    /// Absolute `(address, size)` of each bank declared by `regions`, in ascending order.
+    /// Defaults to the single bank `FLASH_ADDRESS..FLASH_ADDRESS + FLASH_SIZE`. Addresses in
+    /// the gaps between banks lie outside the flash even though `FLASH_SIZE` spans them.
+    const REGIONS: &'static [(u32, u32)] = &[(Self::FLASH_ADDRESS, Self::FLASH_SIZE)];
+    /// Erased contents, repeated from `FLASH_ADDRESS` on, as declared by `empty_pattern`.
+    /// Defaults to `EMPTY_VALUE` alone.
+    const EMPTY_PATTERN: &'static [u8] = &[Self::EMPTY_VALUE];
+    /// Largest write the controller accepts at once, as declared by `write_chunk`. Defaults to
+    /// `PAGE_SIZE`. See [`program_chunked`].
+    const WRITE_CHUNK: u32 = Self::PAGE_SIZE;
+    /// Secondary region declared by `data_region`, outside the main flash described above.
+    const DATA_REGION: Option<DataRegion> = None;
+    /// Byte order of the words assembled from page data, as declared by `word_order`.
+    /// Defaults to [`WordOrder::LittleEndian`].
+    const WORD_ORDER: WordOrder = WordOrder::LittleEndian;
+    /// Spare-area bytes of ECC per page, as declared by `ecc_size`. Defaults to 3 bytes per
+    /// 256 bytes of page, matching the default [`FlashOps::compute_ecc`].
+    #[cfg(feature = "ecc")]
+    const ECC_SIZE: u32 = 3 * Self::PAGE_SIZE.div_ceil(256);
+
+    /// Counter advanced by one for each sector the crate-provided `erase_chip` and
+    /// `erase_range` loops erase, which `flash_algorithm!` exports as `FlashEraseProgress`.
+    /// Defaults to `None` for geometries implemented by hand. Overrides of those methods may
+    /// advance it themselves with `fetch_add`.
+    #[cfg(feature = "erase-progress")]
+    fn erase_progress() -> Option<&'static core::sync::atomic::AtomicU32> {
+        None
+    }
+
+    /// Returns the erased value of the byte at `address`, following [`Self::EMPTY_PATTERN`].
+    fn empty_byte(address: u32) -> u8 {
+        let pattern = Self::EMPTY_PATTERN;
+        pattern[(address.wrapping_sub(Self::FLASH_ADDRESS) as usize) % pattern.len()]
+    }
+
+    /// Returns whether `address..address + size` lies within one bank of the flash.
+    fn contains(address: u32, size: u32) -> bool
+    where
+        Self: Sized,
+    {
+        in_bounds::<Self>(address, size)
+    }
+
+    /// Returns whether `address` sits on a page boundary.
+    fn is_page_aligned(address: u32) -> bool {
+        address.is_multiple_of(Self::PAGE_SIZE)
+    }
+
+    /// Rounds `address` down to the start of its page.
+    fn page_base(address: u32) -> u32 {
+        address - address % Self::PAGE_SIZE
+    }
+
+    /// Fails with [`ErrorCode::UnalignedProgram`] unless `address` is page aligned and `len` is
+    /// a whole number of pages.
+    fn check_page_span(address: u32, len: usize) -> Result<(), Error> {
+        if Self::is_page_aligned(address) && (len as u32).is_multiple_of(Self::PAGE_SIZE) {
+            Ok(())
+        } else {
+            Err(ErrorCode::UnalignedProgram.into())
+        }
+    }
+}
+
+/// Rust-native view of the geometry in the generated `FlashDeviceInfo` descriptor, usable in
+/// `const` contexts and host tests. `flash_algorithm!` emits it as `FLASH_PROPERTIES`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FlashProperties {
+    pub address: u32,
+    pub size: u32,
+    pub page_size: u32,
+    pub empty_value: u8,
+    pub program_timeout: u32,
+    pub erase_timeout: u32,
+    /// `(size, address)` sector entries, addresses relative to `address`, without the
+    /// terminating sentinel.
+    pub sectors: &'static [(u32, u32)],
+}
+
+impl FlashProperties {
+    pub const fn of<A: FlashGeometry>() -> Self {
+        Self {
+            address: A::FLASH_ADDRESS,
+            size: A::FLASH_SIZE,
+            page_size: A::PAGE_SIZE,
+            empty_value: A::EMPTY_VALUE,
+            program_timeout: A::PROGRAM_TIMEOUT,
+            erase_timeout: A::ERASE_TIMEOUT,
+            sectors: A::SECTORS,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub const fn in_bounds<A: FlashGeometry>(address: u32, size: u32) -> bool {
+    let mut i = 0;
+    while i < A::REGIONS.len() {
+        let (start, len) = A::REGIONS[i];
+        if address >= start && (address - start) as u64 + size as u64 <= len as u64 {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Returns the absolute `(base, size)` of the sector of `A` containing `address`, or `None`
+/// outside its banks.
+const fn sector_at<A: FlashGeometry>(address: u32) -> Option<(u32, u32)> {
+    if !in_bounds::<A>(address, 1) {
+        return None;
+    }
+    sector_containing(A::SECTORS, A::FLASH_ADDRESS, A::FLASH_SIZE, address)
+}
+
+/// Returns the sector of `A` containing `address`, or the first one after it when `address`
+/// lies before the first sector or in a gap between banks, for walking every sector in
+/// address order.
+#[cfg(any(feature = "erase-chip", feature = "estimate"))]
+fn sector_from<A: FlashGeometry>(address: u64) -> Option<(u32, u32)> {
+    let first = A::FLASH_ADDRESS as u64 + A::SECTORS.first()?.1 as u64;
+    let address = core::cmp::max(address, first);
+    let &(start, _) = A::REGIONS
+        .iter()
+        .find(|&&(start, size)| address < start as u64 + size as u64)?;
+    sector_at::<A>(core::cmp::max(address, start as u64) as u32)
+}
+
+/// Returns how many sectors entry `i` of `A::SECTORS` describes, up to the next entry or the
+/// end of the bank it starts in, whichever comes first.
+#[cfg(feature = "erase-step")]
+fn entry_sector_count<A: FlashGeometry>(i: usize) -> u32 {
+    let (size, start) = A::SECTORS[i];
+    let base = A::FLASH_ADDRESS as u64 + start as u64;
+    let next = A::SECTORS
+        .get(i + 1)
+        .map_or(A::FLASH_SIZE, |&(_, next)| next);
+    let end = A::REGIONS
+        .iter()
+        .map(|&(start, size)| (start as u64, start as u64 + size as u64))
+        .find(|&(start, end)| start <= base && base < end)
+        .map_or(base, |(_, end)| {
+            core::cmp::min(end, A::FLASH_ADDRESS as u64 + next as u64)
+        });
+    (end - base).div_ceil(size as u64) as u32
+}
+
+/// Returns the absolute `(base, size)` of the sector containing `address` in a CMSIS sector
+/// table, where each entry's size applies up to the next entry or the end of the flash.
+#[doc(hidden)]
+pub const fn sector_containing(
+    sectors: &[(u32, u32)],
+    flash_address: u32,
+    flash_size: u32,
+    address: u32,
+) -> Option<(u32, u32)> {
+    if address < flash_address || address - flash_address >= flash_size {
+        return None;
+    }
+    let offset = address - flash_address;
+    let mut i = sectors.len();
+    while i > 0 {
+        i -= 1;
+        let (size, start) = sectors[i];
+        if start <= offset {
+            return Some((flash_address + start + (offset - start) / size * size, size));
+        }
+    }
+    None
+}
+
+/// Copies `data` into a fixed `[u8; N]`, for `program_page` implementations that work on a
+/// page-sized stack buffer. Returns [`ErrorCode::InvalidArgument`] unless `data` is exactly
+/// `N` bytes long, so a short final chunk must be padded first, see [`program_padded`].
+///
+/// The array only has the alignment of `u8`. Controllers needing word accesses should read
+/// words with [`read_u32_le`] or [`WordOrder::read_u32`], or copy into an aligned wrapper.
+#[inline]
+pub fn copy_page<const N: usize>(data: &[u8]) -> Result<[u8; N], Error> {
+    data.try_into()
+        .map_err(|_| ErrorCode::InvalidArgument.into())
+}
+
+/// Order in which the bytes of page data are assembled into the words written to the flash,
+/// as declared by the `word_order` key of [`flash_algorithm!`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum WordOrder {
+    /// The first byte is the least significant, as on most microcontroller buses.
+    #[default]
+    LittleEndian,
+    /// The first byte is the most significant, for big-endian buses or word-swapped parallel
+    /// flash.
+    BigEndian,
+}
+
+impl WordOrder {
+    /// Reads the `u16` at byte `offset` of `data`, or `None` if it does not fit.
+    pub const fn read_u16(self, data: &[u8], offset: usize) -> Option<u16> {
+        match self {
+            WordOrder::LittleEndian => read_u16_le(data, offset),
+            WordOrder::BigEndian => read_u16_be(data, offset),
+        }
+    }
+
+    /// Reads the `u32` at byte `offset` of `data`, or `None` if it does not fit.
+    pub const fn read_u32(self, data: &[u8], offset: usize) -> Option<u32> {
+        match self {
+            WordOrder::LittleEndian => read_u32_le(data, offset),
+            WordOrder::BigEndian => read_u32_be(data, offset),
+        }
+    }
+}
+
+/// Reads the little-endian `u16` at byte `offset` of `data`, or `None` if it does not fit.
+pub const fn read_u16_le(data: &[u8], offset: usize) -> Option<u16> {
+    match read_bytes::<2>(data, offset) {
+        Some(bytes) => Some(u16::from_le_bytes(bytes)),
+        None => None,
+    }
+}
+
+/// Reads the big-endian `u16` at byte `offset` of `data`, or `None` if it does not fit.
+pub const fn read_u16_be(data: &[u8], offset: usize) -> Option<u16> {
+    match read_bytes::<2>(data, offset) {
+        Some(bytes) => Some(u16::from_be_bytes(bytes)),
+        None => None,
+    }
+}
+
+/// Reads the little-endian `u32` at byte `offset` of `data`, or `None` if it does not fit.
+pub const fn read_u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    match read_bytes::<4>(data, offset) {
+        Some(bytes) => Some(u32::from_le_bytes(bytes)),
+        None => None,
+    }
+}
+
+/// Reads the big-endian `u32` at byte `offset` of `data`, or `None` if it does not fit.
+pub const fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    match read_bytes::<4>(data, offset) {
+        Some(bytes) => Some(u32::from_be_bytes(bytes)),
+        None => None,
+    }
+}
+
+const fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    if offset > data.len() || data.len() - offset < N {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    let mut i = 0;
+    while i < N {
+        bytes[i] = data[offset + i];
+        i += 1;
+    }
+    Some(bytes)
+}
+
+/// Programs `data` through [`FlashOps::program_chunk`] in pieces of at most
+/// [`FlashGeometry::WRITE_CHUNK`] bytes that never cross a chunk boundary, calling
+/// [`FlashOps::wait_chunk`] after each one. Chunk boundaries are counted from
+/// `FLASH_ADDRESS`, so an `address` below it, or a range running past the end of the address
+/// space, fails with [`ErrorCode::AddressOutOfRange`]. A `WRITE_CHUNK` of `0` fails the build.
+///
+/// Call it from `program_page` on controllers whose write FIFO is smaller than a page:
+///
+/// ```ignore
+/// fn program_page(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+///     flashops::program_chunked(self, address, data)
+/// }
+/// ```
+pub fn program_chunked<A: FlashOps + FlashGeometry>(
+    algo: &mut A,
+    address: u32,
+    data: &[u8],
+) -> Result<(), Error> {
+    const { assert!(A::WRITE_CHUNK > 0, "WRITE_CHUNK must not be zero") };
+    if address < A::FLASH_ADDRESS || address as u64 + data.len() as u64 > 1 << 32 {
+        return Err(ErrorCode::AddressOutOfRange.into());
+    }
+    let mut address = address;
+    let mut data = data;
+    while !data.is_empty() {
+        let room = A::WRITE_CHUNK - (address - A::FLASH_ADDRESS) % A::WRITE_CHUNK;
+        let (chunk, rest) = data.split_at(core::cmp::min(room as usize, data.len()));
+        algo.program_chunk(address, chunk)?;
+        algo.wait_chunk()?;
+        algo.feed_watchdog();
+        data = rest;
+        // Only advance while data remains, so a range ending at `u32::MAX` does not overflow.
+        if !data.is_empty() {
+            address += chunk.len() as u32;
+        }
+    }
+    Ok(())
+}
+
+/// Largest [`FlashGeometry::ECC_SIZE`] supported, bounding the stack buffer the `program_page`
+/// entry point computes ECC into.
+#[cfg(feature = "ecc")]
+pub const MAX_ECC_SIZE: usize = 256;
+
+/// Programs a main-flash page for the `program_page` entry point: through
+/// [`FlashOps::program_page`], or with the `ecc` feature through [`FlashOps::compute_ecc`] and
+/// [`FlashOps::program_page_ecc`].
+#[doc(hidden)]
+pub fn program_main<A: FlashOps + FlashGeometry>(
+    algo: &mut A,
+    address: u32,
+    data: &[u8],
+) -> Result<(), Error> {
+    #[cfg(feature = "ecc")]
+    {
+        const {
+            assert!(
+                A::ECC_SIZE as usize <= MAX_ECC_SIZE,
+                "ecc_size larger than MAX_ECC_SIZE"
+            )
+        };
+        let mut buffer = [0u8; MAX_ECC_SIZE];
+        let ecc = &mut buffer[..A::ECC_SIZE as usize];
+        algo.compute_ecc(data, ecc);
+        algo.program_page_ecc(address, data, ecc)
+    }
+    #[cfg(not(feature = "ecc"))]
+    algo.program_page(address, data)
+}
+
+/// Calls `program_page` with `data` extended with the empty value up to the end of the page
+/// containing `address`, following [`FlashGeometry::EMPTY_PATTERN`], so the algorithm always
+/// writes whole pages even when the host sends a short final chunk. With the `ecc` feature it
+/// programs the padded page with its ECC instead, so the code covers the whole page.
+///
+/// `N` is the size of the stack buffer and must be at least `A::PAGE_SIZE`. Data that already
+/// reaches the end of its page is passed through unchanged.
+#[inline(never)]
+pub fn program_padded<A: FlashOps + FlashGeometry, const N: usize>(
+    algo: &mut A,
+    address: u32,
+    data: &[u8],
+) -> Result<(), Error> {
+    const {
+        assert!(
+            N >= A::PAGE_SIZE as usize,
+            "padding buffer smaller than a page"
+        )
+    };
+    let remaining = (A::PAGE_SIZE - address % A::PAGE_SIZE) as usize;
+    if data.len() >= remaining {
+        return program_main(algo, address, data);
+    }
+    let mut page = [A::EMPTY_VALUE; N];
+    for (offset, byte) in page[..remaining].iter_mut().enumerate() {
+        // `address + remaining` is the end of the page, at most `u32::MAX + 1`.
+        *byte = A::empty_byte(address + offset as u32);
     }
+    page[..data.len()].copy_from_slice(data);
+    program_main(algo, address, &page[..remaining])
+}
+
+/// Clears [`FlashGeometry::erase_progress`], called by the `erase_chip` and `erase_range`
+/// entry points before they start, and by `erase_chip_step` on the first step.
+#[doc(hidden)]
+pub fn reset_erase_progress<A: FlashGeometry>() {
+    #[cfg(feature = "erase-progress")]
+    if let Some(progress) = A::erase_progress() {
+        progress.store(0, core::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(any(
+    feature = "erase-chip",
+    feature = "erase-range",
+    feature = "erase-step"
+))]
+fn advance_erase_progress<A: FlashGeometry>() {
+    #[cfg(feature = "erase-progress")]
+    if let Some(progress) = A::erase_progress() {
+        progress.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// Iterator over the page base addresses of a sector, returned by [`pages_in`].
+#[derive(Debug, Clone)]
+pub struct Pages {
+    next: u64,
+    end: u64,
+    page_size: u32,
+}
+
+impl Iterator for Pages {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.next >= self.end {
+            return None;
+        }
+        let page = self.next as u32;
+        self.next += self.page_size as u64;
+        Some(page)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Pages {
+    fn len(&self) -> usize {
+        if self.next >= self.end {
+            return 0;
+        }
+        (self.end - self.next).div_ceil(self.page_size as u64) as usize
+    }
+}
+
+impl core::iter::FusedIterator for Pages {}
+
+/// Returns the base address of each `page_size` page of the sector
+/// `sector_base..sector_base + sector_size`, in increasing order.
+///
+/// When `sector_size` is not a multiple of `page_size` the last page yielded starts inside
+/// the sector but extends past its end, so callers programming or erasing whole pages must
+/// clamp it. A `page_size` of `0` yields nothing, and a sector reaching or running past the end
+/// of the address space stops at its last page below `2^32` instead of wrapping around.
+///
+/// ```
+/// let pages: Vec<u32> = flashops::pages_in(0x0800_0000, 0x1000, 0x400).collect();
+/// assert_eq!(pages, [0x0800_0000, 0x0800_0400, 0x0800_0800, 0x0800_0C00]);
+/// ```
+pub const fn pages_in(sector_base: u32, sector_size: u32, page_size: u32) -> Pages {
+    let end = if page_size == 0 {
+        sector_base as u64
+    } else if sector_base as u64 + sector_size as u64 > 1 << 32 {
+        1 << 32
+    } else {
+        sector_base as u64 + sector_size as u64
+    };
+    Pages {
+        next: sector_base as u64,
+        end,
+        page_size,
+    }
+}
+
+/// Returns the `(size, base)` of the sector containing the absolute `address`, in the same
+/// order as the entries of [`FlashGeometry::SECTORS`], or `None` outside the flash and in the
+/// gaps between its banks.
+pub const fn sector_for_address<A: FlashGeometry>(address: u32) -> Option<(u32, u32)> {
+    match sector_at::<A>(address) {
+        Some((base, size)) => Some((size, base)),
+        None => None,
+    }
+}
+
+/// Rounds the absolute `address` down to the start of its sector, or returns `None` outside the
+/// flash.
+pub const fn sector_aligned_base<A: FlashGeometry>(address: u32) -> Option<u32> {
+    match sector_for_address::<A>(address) {
+        Some((_, base)) => Some(base),
+        None => None,
+    }
+}
+
+/// An absolute flash address, for keeping addresses apart from sector-relative offsets inside
+/// algorithm code. The entry points and [`FlashOps`] keep taking plain `u32`s.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct FlashAddress(pub u32);
+
+impl FlashAddress {
+    pub const fn new(address: u32) -> Self {
+        Self(address)
+    }
+
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the offset of this address from `base`, or `None` when it lies below `base`.
+    pub const fn offset_from_base(self, base: u32) -> Option<u32> {
+        self.0.checked_sub(base)
+    }
+
+    pub const fn is_page_aligned(self, page_size: u32) -> bool {
+        self.0.is_multiple_of(page_size)
+    }
+
+    /// Returns the address `offset` bytes further on, or `None` on overflow.
+    pub const fn checked_add(self, offset: u32) -> Option<Self> {
+        match self.0.checked_add(offset) {
+            Some(address) => Some(Self(address)),
+            None => None,
+        }
+    }
+
+    /// Returns the zero-based number of the sector containing this address, counting every
+    /// sector of the device in address order, for a CMSIS sector table as used by
+    /// [`FlashGeometry::SECTORS`].
+    pub const fn sector_index(
+        self,
+        sectors: &[(u32, u32)],
+        flash_address: u32,
+        flash_size: u32,
+    ) -> Option<u32> {
+        if sector_containing(sectors, flash_address, flash_size, self.0).is_none() {
+            return None;
+        }
+        let offset = self.0 - flash_address;
+        let mut index = 0;
+        let mut i = 0;
+        while i < sectors.len() {
+            let (size, start) = sectors[i];
+            let end = if i + 1 < sectors.len() {
+                sectors[i + 1].1
+            } else {
+                flash_size
+            };
+            if offset < end {
+                return Some(index + (offset - start) / size);
+            }
+            index += (end - start) / size;
+            i += 1;
+        }
+        None
+    }
+}
+
+impl From<u32> for FlashAddress {
+    fn from(address: u32) -> Self {
+        Self(address)
+    }
+}
+
+impl From<FlashAddress> for u32 {
+    fn from(address: FlashAddress) -> Self {
+        address.0
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Operation {
+    Erase = 1,
+    Program = 2,
+    Verify = 3,
+}
+
+impl TryFrom<u32> for Operation {
+    type Error = Error;
+
+    /// Maps a CMSIS function code to an [`Operation`], rejecting unknown codes with
+    /// [`ErrorCode::InvalidOperation`].
+    fn try_from(code: u32) -> Result<Self, Error> {
+        match code {
+            ERASE => Ok(Operation::Erase),
+            PROGRAM => Ok(Operation::Program),
+            VERIFY => Ok(Operation::Verify),
+            _ => Err(ErrorCode::InvalidOperation.into()),
+        }
+    }
+}
+
+impl core::fmt::Display for Operation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Operation::Erase => "erase",
+            Operation::Program => "program",
+            Operation::Verify => "verify",
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(u16)]
+pub enum DeviceType {
+    Unknown = 0,
+    OnChip = 1,
+    Ext8Bit = 2,
+    Ext16Bit = 3,
+    Ext32Bit = 4,
+    ExtSpi = 5,
+}
+
+/// Outcome of one call to [`FlashOps::erase_chip_step`].
+#[cfg(feature = "erase-step")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum StepResult {
+    /// More steps remain; call again.
+    InProgress,
+    /// The operation finished with this step.
+    Done,
+}
+
+#[doc(hidden)]
+pub struct AlgoInstance<T> {
+    instance: core::cell::UnsafeCell<core::mem::MaybeUninit<T>>,
+    operation: core::cell::UnsafeCell<Operation>,
+    busy: core::sync::atomic::AtomicBool,
+    unlocked: core::sync::atomic::AtomicBool,
+    #[cfg(feature = "erase-step")]
+    step: core::cell::UnsafeCell<u32>,
+}
+
+// Flash loaders call into the algorithm from a single thread of execution.
+unsafe impl<T> Sync for AlgoInstance<T> {}
+
+impl<T> AlgoInstance<T> {
+    pub const fn new() -> Self {
+        Self {
+            instance: core::cell::UnsafeCell::new(core::mem::MaybeUninit::uninit()),
+            operation: core::cell::UnsafeCell::new(Operation::Erase),
+            busy: core::sync::atomic::AtomicBool::new(false),
+            unlocked: core::sync::atomic::AtomicBool::new(false),
+            #[cfg(feature = "erase-step")]
+            step: core::cell::UnsafeCell::new(0),
+        }
+    }
+
+    pub fn as_mut_ptr(&self) -> *mut T {
+        self.instance.get().cast()
+    }
+
+    pub fn enter(&self) -> Option<BusyGuard<'_>> {
+        // Load and store rather than swap: not every target has atomic read-modify-write.
+        if self.busy.load(core::sync::atomic::Ordering::Relaxed) {
+            return None;
+        }
+        self.busy.store(true, core::sync::atomic::Ordering::Relaxed);
+        Some(BusyGuard(&self.busy))
+    }
+
+    /// # Safety
+    ///
+    /// Must not race with another access to the instance.
+    pub unsafe fn operation(&self) -> Operation {
+        *self.operation.get()
+    }
+
+    /// # Safety
+    ///
+    /// Must not race with another access to the instance.
+    pub unsafe fn set_operation(&self, operation: Operation) {
+        *self.operation.get() = operation;
+        #[cfg(feature = "erase-step")]
+        {
+            *self.step.get() = 0;
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Must not race with another access to the instance.
+    #[cfg(feature = "erase-step")]
+    pub unsafe fn step(&self) -> u32 {
+        *self.step.get()
+    }
+
+    /// # Safety
+    ///
+    /// Must not race with another access to the instance.
+    #[cfg(feature = "erase-step")]
+    pub unsafe fn set_step(&self, step: u32) {
+        *self.step.get() = step;
+    }
+
+    /// Returns a pointer to the instance, calling [`FlashOps::unlock`] first unless it already
+    /// succeeded since the instance was created.
+    ///
+    /// # Safety
+    ///
+    /// The instance must be constructed, and this must not race with another access to it.
+    pub unsafe fn unlocked(&self) -> Result<*mut T, Error>
+    where
+        T: FlashOps,
+    {
+        let instance = self.as_mut_ptr();
+        if !self.unlocked.load(core::sync::atomic::Ordering::Relaxed) {
+            (*instance).unlock()?;
+            self.unlocked
+                .store(true, core::sync::atomic::Ordering::Relaxed);
+        }
+        Ok(instance)
+    }
+
+    /// Calls [`FlashOps::lock`] if the instance was unlocked.
+    ///
+    /// # Safety
+    ///
+    /// The instance must be constructed, and this must not race with another access to it.
+    pub unsafe fn relock(&self) -> Result<(), Error>
+    where
+        T: FlashOps,
+    {
+        if !self.unlocked.load(core::sync::atomic::Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.unlocked
+            .store(false, core::sync::atomic::Ordering::Relaxed);
+        (*self.as_mut_ptr()).lock()
+    }
+}
+
+#[doc(hidden)]
+pub struct BusyGuard<'a>(&'a core::sync::atomic::AtomicBool);
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, core::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+impl<T> Default for AlgoInstance<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[doc(hidden)]
+pub struct Stack<const N: usize>(core::cell::UnsafeCell<[u8; N]>);
+
+unsafe impl<const N: usize> Sync for Stack<N> {}
+
+impl<const N: usize> Stack<N> {
+    pub const fn new() -> Self {
+        Self(core::cell::UnsafeCell::new([0; N]))
+    }
+}
+
+impl<const N: usize> Default for Stack<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Zeroed RAM the host stages page data in before calling `program_page`, emitted as the
+/// `FlashAlgorithmBuffer` symbol by the `transfer_buffer` key of [`flash_algorithm!`].
+///
+/// The `N` bytes are split into `page_buffers` equal slots, one per page in flight.
+#[repr(transparent)]
+pub struct TransferBuffer<const N: usize>(core::cell::UnsafeCell<[u8; N]>);
+
+// Only the host writes the buffer, while the algorithm is halted.
+unsafe impl<const N: usize> Sync for TransferBuffer<N> {}
+
+impl<const N: usize> TransferBuffer<N> {
+    pub const fn new() -> Self {
+        Self(core::cell::UnsafeCell::new([0; N]))
+    }
+
+    pub const fn as_ptr(&self) -> *mut u8 {
+        self.0.get().cast()
+    }
+
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Returns whether the `len` bytes at `data` lie entirely within the buffer.
+    pub fn contains(&self, data: *const u8, len: usize) -> bool {
+        let start = self.as_ptr() as usize;
+        let data = data as usize;
+        data >= start && data - start <= N && len <= N - (data - start)
+    }
+}
+
+impl<const N: usize> Default for TransferBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[doc(hidden)]
+pub const fn device_name(name: &str) -> [u8; 128] {
+    let bytes = name.as_bytes();
+    if bytes.len() > 127 {
+        panic!("device_name must be at most 127 bytes long");
+    }
+    let mut dev_name = [0u8; 128];
+    let mut i = 0;
+    while i < bytes.len() {
+        dev_name[i] = bytes[i];
+        i += 1;
+    }
+    dev_name
+}
+
+/// Conservative `(program, erase)` timeouts in milliseconds for the given geometry.
+///
+/// Budgets 5 ms per started 256 bytes of page and 100 ms per started 4 KiB of sector, which
+/// covers typical NOR datasheet maximums. The result can be passed to the `program_timeout`
+/// and `erase_timeout` keys of [`flash_algorithm!`].
+pub const fn recommended_timeouts(page_size: u32, max_sector_size: u32) -> (u32, u32) {
+    let program = page_size.div_ceil(256) * 5;
+    let erase = max_sector_size.div_ceil(4096) * 100;
+    (
+        if program == 0 { 1 } else { program },
+        if erase == 0 { 1 } else { erase },
+    )
+}
+
+#[doc(hidden)]
+pub const fn max_timeout(timeouts: &[u32]) -> u32 {
+    let mut max = 0;
+    let mut i = 0;
+    while i < timeouts.len() {
+        if timeouts[i] > max {
+            max = timeouts[i];
+        }
+        i += 1;
+    }
+    max
+}
+
+#[doc(hidden)]
+pub const fn timeout(ms: u32) -> u32 {
+    if ms == 0 {
+        panic!("program_timeout and erase_timeout must be non-zero");
+    }
+    ms
+}
+
+/// Returns whether `address..address + size` overlaps any of the sectors starting at the
+/// absolute addresses in `protected`.
+#[doc(hidden)]
+pub const fn overlaps_protected<A: FlashGeometry>(
+    protected: &[u32],
+    address: u32,
+    size: u32,
+) -> bool {
+    let end = address as u64 + size as u64;
+    let mut i = 0;
+    while i < protected.len() {
+        if let Some((sector_size, base)) = sector_for_address::<A>(protected[i]) {
+            if (base as u64) < end && (address as u64) < base as u64 + sector_size as u64 {
+                return true;
+            }
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Returns whether the sector table entry starting at `address` ends no later than the next
+/// entry begins, which also rejects out-of-order and duplicate entries.
+#[doc(hidden)]
+pub const fn sector_ends_before_next(sectors: &[(u32, u32)], address: u32) -> bool {
+    let mut i = 0;
+    while i < sectors.len() {
+        let (size, start) = sectors[i];
+        if start == address {
+            return i + 1 == sectors.len() || start as u64 + size as u64 <= sectors[i + 1].1 as u64;
+        }
+        i += 1;
+    }
+    false
+}
+
+#[doc(hidden)]
+pub const fn regions_size(regions: &[(u32, u32)]) -> u32 {
+    let (last_addr, last_size) = regions[regions.len() - 1];
+    last_addr + last_size - regions[0].0
+}
+
+/// Returns whether each `(address, size)` region ends at or before the next one begins.
+#[doc(hidden)]
+pub const fn regions_ascending(regions: &[(u32, u32)]) -> bool {
+    let mut i = 1;
+    while i < regions.len() {
+        let (address, size) = regions[i - 1];
+        if address as u64 + size as u64 > regions[i].0 as u64 {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Generates the CMSIS entry points and `FlashDeviceInfo` descriptor for `$algo`.
+///
+/// Besides the entry points, the macro emits `current_operation()`, returning the
+/// [`Operation`] passed to `initialize` so methods can configure the controller per phase, and
+/// `SECTORS`, the `SECTOR_COUNT` sector entries without the terminator that `FlashDeviceInfo`
+/// appends for CMSIS tooling.
+///
+/// The entry points return [`ErrorCode::NotInitialized`] when called before a successful
+/// `initialize`: one whose `create` fails leaves the algorithm uninitialized, even if an
+/// instance existed before the call. `initialize` returns `InvalidOperation` for an unknown
+/// function code and `WrongDevice` when the connected part's ID is not among `allowed_ids`, and
+/// `create` may return `UnsupportedOperation` to refuse an [`Operation`] the device cannot
+/// perform. With the `reentrancy-guard` feature every entry point returns `Busy` when called
+/// while another one is still running. `deinitialize` returns `NotInitialized` when there is no
+/// instance to tear down, and otherwise the first error from [`FlashOps::lock`],
+/// [`FlashOps::uninit`] and [`FlashOps::deinit`]. `verify` and `verify_pages` return
+/// `InvalidArgument` for a region that wraps around the address space, and every entry point
+/// that writes through a pointer, such as `read`, `checksum` or `erase_chip_step`, returns it
+/// for a null one, as does `verify_pages` for a null CRC pointer. A zero-length
+/// `program_page`, `program_pages`, `verify` or `erase_range`, or a `verify_pages` call with no
+/// pages, returns `0` without calling into the algorithm.
+///
+/// The `erase_chip` and `verify` entry points are emitted even when their features are
+/// disabled, returning `UnsupportedOperation`, unless `no-unsupported-stubs` is enabled;
+/// `full-symbol-table` emits such stubs for every optional entry point.
+///
+/// Sector entries must be listed by increasing address, each ending within `flash_size` and no
+/// later than the next entry begins; violations fail the build naming the offending entry.
+///
+/// An entry may add `program_timeout: 3000` to override the page program timeout for the
+/// sectors it describes, as in `{size: 0x20000, address: 0x20000, program_timeout: 3000}`.
+/// The descriptor's `program_time_out` keeps the global value, and the macro emits the
+/// per-entry timeouts, in milliseconds, as `SECTOR_PROGRAM_TIMEOUTS` and the `u32` array
+/// symbol `FlashSectorTimeouts` next to `FlashDeviceInfo`, with their maximum in
+/// `MAX_PROGRAM_TIMEOUT`. To find the timeout for an address, the host picks the last sector
+/// entry whose address is at or below the address's offset from `flash_address`, and reads
+/// the word at the same index; hosts that ignore the symbol can wait for the maximum.
+///
+/// Optional keys may follow `sectors`, in this order:
+///
+/// - `device_name: "..."`: name reported by tooling, at most 127 bytes. Defaults to all zeros.
+/// - `device_type: DeviceType::OnChip`: a [`DeviceType`] or raw CMSIS code. Defaults to `5`.
+/// - `program_timeout: 1000`: page program timeout in milliseconds, non-zero. Defaults to `1000`.
+/// - `erase_timeout: 2000`: sector erase timeout in milliseconds, non-zero. Defaults to `2000`.
+/// - `stack_size: 1024`: size in bytes of the zeroed `FlashAlgorithmStack` symbol reserved in
+///   the `PrgDataStack` section, whose end the host can use as the initial stack pointer.
+///   Defaults to `512`.
+/// - `ram_buffer_size: 0x1000`: bytes of target RAM the host must reserve for the data passed
+///   to `program_page`. Defaults to `page_size * page_buffers`.
+/// - `page_buffers: 2`: number of page buffers the host may alternate between, so it can
+///   transfer the next page while the previous one is programmed. Defaults to `1`. Each
+///   `program_page` call receives a pointer to whichever buffer holds its data, so
+///   implementations need no knowledge of the buffer in use.
+/// - `version: 0x0101`: the `u16` CMSIS version word stored in `vers`. Defaults to `0x0`.
+/// - `device_address: 0x9000_0000`: the `dev_addr` reported to tooling. Defaults to
+///   `flash_address`. Tooling treats `dev_addr` as the flash's location in the memory map
+///   and adds the (relative) sector table addresses to it, while `flash_address` stays the
+///   base of [`FlashGeometry`] and the bounds checks. Set it when the algorithm programs
+///   through an indirect interface whose addresses differ from the memory-mapped window.
+/// - `clock_range: {min: 8_000_000, max: 64_000_000}`: inclusive range of the `clock` passed
+///   to `initialize`, which returns [`ErrorCode::ClockOutOfRange`] before calling `create`
+///   when the host supplies a clock outside it.
+/// - `init_retries: 3`: how many times `initialize` calls `create` before returning its last
+///   error, calling [`FlashOps::retry_delay`] in between. Defaults to `1`.
+/// - `entry_section: ".flash_entry"`: string literal overriding the section of every entry
+///   point.
+/// - `device_section: "DevDscr"`: string literal overriding the section of `FlashDeviceInfo`
+///   and `FlashAlgorithmInfo`.
+/// - `pad_pages: true`: pad writes that stop short of the end of their page with the empty
+///   value before calling `program_page`, see [`program_padded`]. Defaults to `false`, in
+///   which case `program_page` receives exactly the bytes the host sent and must handle a
+///   short final chunk itself.
+/// - `protected_sectors: [0x0800_0000, ...]`: absolute base addresses of sectors the entry
+///   points refuse to touch. `erase_sector`, `program_page`, `program_pages` and `erase_range`
+///   return [`ErrorCode::SectorProtected`] when the request overlaps one, and `erase_chip`
+///   whenever any is declared. This is advisory protection in the generated shims only: it
+///   does not configure the hardware, and calls made from inside the algorithm are not
+///   checked.
+/// - `program_granularity: 2`: smallest unit in bytes, 1, 2 or 4, that the flash programs.
+///   The `program_page` entry point returns [`ErrorCode::UnalignedProgram`] for a size that is
+///   not a multiple of it. Defaults to `1`.
+/// - `log_buffer: 256`: with the `log` feature, emits a `LogBuffer` of this many bytes as the
+///   `FlashAlgorithmLog` symbol, next to the algorithm state. Forward `FlashOps::log` to its
+///   `FlashLog::write` to read progress messages from the host. Must not be zero.
+/// - `empty_pattern: [0xFF, 0xFF, 0x00, 0x00]`: erased contents repeating from `flash_address`
+///   on, for flashes whose blank state is not a single byte value. It becomes
+///   [`FlashGeometry::EMPTY_PATTERN`] and drives the crate-provided blank checks and padding;
+///   the descriptors keep the single-byte `empty_value`, since their `empty` field holds one
+///   byte. Its length must divide `page_size`.
+/// - `data_region: {address: 0x0808_0000, size: 0x1000, page_size: 4, program_granularity: 1}`:
+///   a secondary region, such as EEPROM-emulation data flash, outside the main flash and
+///   with its own programming semantics, exposed as [`FlashGeometry::DATA_REGION`];
+///   `program_granularity` defaults to `1`. Requests are routed to it as described below.
+/// - `sector_sentinel: {size: 0, address: 0}`: the entry terminating the sector table of
+///   `FlashDeviceInfo`, for loaders expecting another convention than Keil's. It must not
+///   describe a sector of the flash: its size must be `0` or its address lie past
+///   `flash_size`. `sector_sentinel: none` omits the terminator, which is only safe for
+///   tooling that takes the number of sectors from `FlashAlgorithmInfo`'s `sector_count`
+///   rather than scanning for a terminator. Defaults to
+///   `{size: 0xffff_ffff, address: 0xffff_ffff}`.
+/// - `transfer_buffer: true`: emits a zeroed [`TransferBuffer`] of `ram_buffer_size` bytes as
+///   the `FlashAlgorithmBuffer` symbol in the `PrgDataBuffer` section, giving the host a fixed
+///   place to stage page data. It holds `page_buffers` consecutive slots of
+///   `ram_buffer_size / page_buffers` bytes; a double-buffering host writes the next page into
+///   one slot while the previous one is programmed from the other, passing each slot's address
+///   as the `data` pointer. `program_page` and `program_and_verify` then return
+///   [`ErrorCode::InvalidArgument`] for a size larger than a slot, and `program_pages` for one
+///   larger than the whole buffer. Each slot must hold at least `page_size` bytes.
+/// - `write_chunk: 256`: largest write the controller's FIFO accepts, which must divide
+///   `page_size`. It becomes [`FlashGeometry::WRITE_CHUNK`], used by [`program_chunked`].
+///   Defaults to `page_size`.
+/// - `allowed_ids: [0x20BA19, ...]`: with the `device-id` feature, JEDEC IDs of the parts the
+///   algorithm may run on. After a successful `create` for [`Operation::Erase`] or
+///   [`Operation::Program`], `initialize` reads [`FlashOps::read_device_id`] and, if the ID is
+///   not listed, calls [`FlashOps::deinit`] and returns [`ErrorCode::WrongDevice`], or the
+///   read error. No ID is read when the key is absent, and giving it without the feature fails
+///   the build.
+/// - `ecc_size: 24`: with the `ecc` feature, spare-area bytes of ECC per page, at most
+///   [`MAX_ECC_SIZE`]. It becomes [`FlashGeometry::ECC_SIZE`]. Defaults to 3 bytes per 256
+///   bytes of `page_size`.
+/// - `word_order: WordOrder::BigEndian`: byte order in which page data is assembled into
+///   words, for big-endian buses and word-swapped flash. It becomes
+///   [`FlashGeometry::WORD_ORDER`], honored by `mmio_flash!` with the `mmio` feature. Defaults to
+///   [`WordOrder::LittleEndian`].
+/// - `requires_cache_disable: true`: marks an algorithm that runs from RAM overlapping the
+///   flash's address space, or otherwise cannot run with the flash cache or prefetch buffer
+///   enabled, by setting [`ALGORITHM_FLAG_CACHE_DISABLE`] in the `flags` word of
+///   `FlashAlgorithmInfo` (see below). The host must disable them before calling `initialize`.
+///   Defaults to `false`.
+/// - `buffer_alignment: 4`: alignment in bytes, a power of two, that the `data` buffers the
+///   host passes must have, for controllers that program through DMA. It is reported in the
+///   `buffer_alignment` word of `FlashAlgorithmInfo`, and `program_page`, `program_pages`,
+///   `program_and_verify` and `program_page_counted` return [`ErrorCode::MisalignedBuffer`]
+///   for a `data` pointer that does not meet it. The check is part of the validation that
+///   `no-checks` strips. Defaults to `1`, which accepts any pointer.
+///
+/// Entry points are placed in `.entry`, or in `.text.entry` on `riscv32` so that RISC-V
+/// linker scripts collecting `.text.*` keep them in executable memory. The descriptor is
+/// placed in `DeviceData`. With the `cmsis-sections` feature the layout follows CMSIS `.FLM`
+/// conventions instead: entry points in `PrgCode`, algorithm state in `PrgData` and the
+/// descriptor in `DevDscr`.
+///
+/// For Armv8-M TrustZone devices whose flash controller is only reachable from secure state,
+/// the `trustzone-secure` feature moves the entry points to `.entry.secure`, which the linker
+/// script must place in the secure alias of the RAM the algorithm is loaded to. The debugger
+/// calls the entry points directly with the core halted in secure state, so no
+/// `cmse_nonsecure_entry` gateways are generated; the algorithm cannot be called from
+/// non-secure code. `cmsis-sections` takes precedence over this feature.
+///
+/// Next to `FlashDeviceInfo`, in the same section, the macro emits a `FlashAlgorithmInfo`
+/// symbol for host tooling. It is a `#[repr(C)]` struct of little-endian `u32` words with no
+/// padding:
+///
+/// | Offset | Field                 | Value                                               |
+/// |--------|-----------------------|-----------------------------------------------------|
+/// | 0x00   | `magic`               | [`ALGORITHM_INFO_MAGIC`]                            |
+/// | 0x04   | `version`             | [`ALGORITHM_INFO_VERSION`]                          |
+/// | 0x08   | `flash_address`       | `flash_address`                                     |
+/// | 0x0C   | `flash_size`          | `flash_size`                                        |
+/// | 0x10   | `page_size`           | `page_size`                                         |
+/// | 0x14   | `empty_value`         | `empty_value`, zero-extended                        |
+/// | 0x18   | `ram_buffer_size`     | `ram_buffer_size`                                   |
+/// | 0x1C   | `page_buffers`        | `page_buffers`                                      |
+/// | 0x20   | `program_granularity` | `program_granularity`                               |
+/// | 0x24   | `flags`               | `ALGORITHM_FLAG_*` bits, see below                  |
+/// | 0x28   | `buffer_alignment`    | `buffer_alignment`                                  |
+/// | 0x2C   | `sector_count`        | number of sector entries that follow                |
+/// | 0x30   | `sectors`             | `sector_count` `(size, address)` pairs, no sentinel |
+///
+/// Sector addresses are relative to `flash_address`, as in `FlashDeviceInfo`. `flags` holds
+/// [`ALGORITHM_FLAG_CACHE_DISABLE`] when `requires_cache_disable` is set, and is otherwise
+/// `0`; hosts must ignore bits they do not know. Version 2 of the layout ended with
+/// `program_granularity`, `sector_count` and `sectors`, and version 3 added `flags` only, so
+/// hosts must check `version` before reading past `program_granularity`.
+///
+/// With the `descriptor-v2` feature, the macro also emits `FlashDeviceInfoV2` in the
+/// `DeviceDataV2` section for hosts that understand the extended format, while the classic
+/// `FlashDeviceInfo` stays in place for the others. The two share their fields up to
+/// `erase_time_out`, except that `vers` holds [`FLASH_DEVICE_V2_VERS`] in the extended one, by
+/// which hosts recognise it, rather than the `version` key; four `u32` words follow before the
+/// sector table:
+///
+/// | Field                 | Value                                               |
+/// |-----------------------|-----------------------------------------------------|
+/// | `algo_version`        | `version`, zero-extended                            |
+/// | `clock_min`           | `clock_range` minimum, `0` when not declared        |
+/// | `clock_max`           | `clock_range` maximum, `u32::MAX` when not declared |
+/// | `program_granularity` | `program_granularity`                               |
+///
+/// The macro also emits `DESCRIPTOR_CRC`, the [`descriptor_crc`] of `FlashDeviceInfo`, and
+/// exports it as the `u32` symbol `FlashDescriptorCrc` in the same section, so host tools can
+/// check that a loaded blob matches the target they expect without parsing the descriptor.
+///
+/// The `no-checks` feature strips the validation from every entry point except `initialize`
+/// and `deinitialize`: the `NotInitialized` check, zero-length and argument checks, sector
+/// protection, `strict-erase`, `strict-program` and `bounds-check`. Calls then go straight
+/// into the algorithm, so it is only sound for hosts that never call an entry point before
+/// `initialize` or with a bad range.
+///
+/// With the `std` feature the macro also defines safe `__init`, `__deinit`, `__erase_sector`,
+/// `__program_page` and `__finalize` functions that call the matching entry points under a
+/// process-wide lock, so host tests can exercise the init/deinit state machine and operation
+/// mapping without `unsafe`. Each enabled optional entry point gets one as well: `__erase_chip`,
+/// `__verify`, `__read`, `__blank_check` and `__program_pages`, taking slices where the entry
+/// point takes a pointer and a size. Tests must not mix them with direct calls to the entry
+/// points. Under `std` the entry points and exported statics also keep mangled symbols, so
+/// several invocations can share a test binary without replacing C library functions such as
+/// `read`.
+///
+/// The host selects the `data_region` by address: an `erase_sector` whose address lies in it
+/// calls [`FlashOps::erase_data`], and a `program_page` whose range lies entirely in it calls
+/// [`FlashOps::program_data`], after checking the range against the region's `page_size` and
+/// `program_granularity` instead of the main flash's. `pad_pages` and `protected_sectors`
+/// only apply to the main flash, and the other entry points and the descriptors cover it
+/// alone, so the host takes the region's geometry from its own target description.
+///
+/// With the `strict-erase` feature, `erase_sector` returns [`ErrorCode::UnalignedErase`]
+/// without calling into the algorithm unless the address is the base of a sector declared in
+/// `sectors`, or of a page of the `data_region`. It is off by default for algorithms that
+/// accept addresses inside a sector.
+///
+/// With the `strict-program` feature, `program_page`, `program_and_verify` and
+/// `program_page_counted` return [`ErrorCode::UnalignedProgram`] without calling into the
+/// algorithm when the address is not on a page boundary, counted from `flash_address`. It is
+/// off by default for algorithms that program partial pages on purpose; requests to the
+/// `data_region` are checked against its own pages instead.
+///
+/// With the `erase-progress` feature, the macro emits `FlashEraseProgress`, a `u32` counter
+/// placed with the algorithm state (`PrgData` with `cmsis-sections`) that a host able to read
+/// target memory while an erase runs can poll. `erase_chip` and `erase_range` set it to `0`
+/// when they start, and the crate-provided loops add one after each sector they erase, so it
+/// only grows during an operation and ends at the number of sectors erased. It stays at `0`
+/// for overrides that do not advance [`FlashGeometry::erase_progress`] themselves.
+///
+/// With the `erase-step` feature, the macro emits `erase_chip_step(done: *mut u32) -> u32` for
+/// cooperative loaders: each call runs one [`FlashOps::erase_chip_step`] and returns, writing
+/// `1` to `done` once the chip is erased and `0` while steps remain. The host keeps calling it
+/// until `done` is `1` or it returns an error, and may poll or feed a watchdog in between. An
+/// error or a new `initialize` abandons the erase, and the next call starts over.
+///
+/// With the `ecc` feature, the `program_page` entry point computes [`FlashOps::compute_ecc`]
+/// over the data it receives and passes both to [`FlashOps::program_page_ecc`], which writes
+/// the code to the page's spare area. `page_size`, the sector table and every address the host
+/// sends cover the main area only: the spare area is not addressable, and where the code sits
+/// within it is up to `program_page_ecc`. The code covers exactly the bytes of one call, so an
+/// algorithm that must protect whole pages should also set `pad_pages: true`. The
+/// `data_region` and the other program entry points are unaffected and still reach
+/// [`FlashOps::program_page`].
+///
+/// With the `sector-size` feature, the macro emits
+/// `sector_size(addr: u32, out: *mut u32) -> u32`, which writes
+/// [`FlashOps::sector_size_at`] to `out`. Hosts that find this symbol should query it after
+/// `initialize` for each address they are about to erase, and prefer its answer over the
+/// sector table in `FlashDeviceInfo`, which then only describes the smallest layout the
+/// algorithm was built for.
+///
+/// With the `bounds-check` feature, every entry point taking an address returns
+/// [`ErrorCode::AddressOutOfRange`] without calling into the algorithm when the addressed
+/// range leaves `flash_address..flash_address + flash_size`.
+///
+/// Flashes made of identical sectors can declare them as
+/// `sectors: uniform {size: 0x1000, count: 512}`, with an optional absolute `start:` address
+/// of the first sector (default `flash_address`), as in
+/// `uniform {size: 0x1000, count: 512, start: 0x0800_0000}`. This expands to the single CMSIS
+/// sector entry that describes them, after checking that `count` is non-zero and that the
+/// sectors run from `start` to exactly the end of the flash. Addresses below `start` belong
+/// to no sector.
+///
+/// Devices with several banks can replace `flash_address`, `flash_size` and `sectors` with
+/// `regions: [{address, size, sectors: [...]}, ...]`, listed in ascending address order.
+/// Sector addresses are relative to their own region. The regions are merged into a single
+/// descriptor whose `dev_addr` is the first region's address and whose sector table holds
+/// every region's sectors rebased onto it, followed by the `sector_sentinel` terminator.
+/// As in any CMSIS sector table, an entry's size applies up to the next entry, so a gap
+/// between two regions is described with the size of the last sector before it. The algorithm
+/// itself keeps the banks apart in [`FlashGeometry::REGIONS`]: addresses in a gap fail
+/// `bounds-check`, and the default `erase_chip`, `erase_chip_step` and the sector lookups skip
+/// them.
+#[macro_export]
+macro_rules! flash_algorithm {
+    ($algo:ty, {flash_address: $addr:expr, flash_size: $size:expr, page_size: $page_size:expr, empty_value: $empty:expr, sectors: [$({size: $sector_size:expr, address: $sector_addr:expr $(, program_timeout: $sector_timeout:expr)?}),+]
+        $(, device_name: $name:expr)?
+        $(, device_type: $dev_type:expr)?
+        $(, program_timeout: $program_timeout:expr)?
+        $(, erase_timeout: $erase_timeout:expr)?
+        $(, stack_size: $stack_size:expr)?
+        $(, ram_buffer_size: $ram_buffer_size:expr)?
+        $(, page_buffers: $page_buffers:expr)?
+        $(, version: $version:expr)?
+        $(, device_address: $device_address:expr)?
+        $(, clock_range: {min: $clock_min:expr, max: $clock_max:expr})?
+        $(, init_retries: $init_retries:expr)?
+        $(, entry_section: $entry_section:literal)?
+        $(, device_section: $device_section:literal)?
+        $(, pad_pages: $pad_pages:expr)?
+        $(, protected_sectors: [$($protected:expr),* $(,)?])?
+        $(, program_granularity: $program_granularity:expr)?
+        $(, log_buffer: $log_buffer:expr)?
+        $(, empty_pattern: [$($empty_pattern:expr),+ $(,)?])?
+        $(, data_region: {
+            address: $data_address:expr,
+            size: $data_size:expr,
+            page_size: $data_page_size:expr
+            $(, program_granularity: $data_granularity:expr)?
+        })?
+        $(, sector_sentinel: $sentinel:tt)?
+        $(, transfer_buffer: $transfer_buffer:expr)?
+        $(, write_chunk: $write_chunk:expr)?
+        $(, allowed_ids: [$($allowed_id:expr),+ $(,)?])?
+        $(, ecc_size: $ecc_size:expr)?
+        $(, word_order: $word_order:expr)?
+        $(, requires_cache_disable: $requires_cache_disable:expr)?
+        $(, buffer_alignment: $buffer_alignment:expr)?
+        $(, @$relocatable:ident)?
+        $(, @{regions: [$(($region_start:expr, $region_len:expr)),+]})?
+    }) => {
+        // Set only while `ALGO_INSTANCE` holds a constructed instance, so a failed `create`
+        // never leaves uninitialized memory behind for `deinitialize` to drop.
+        $crate::state! {
+            static INIT_FLAG: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+        }
+        $crate::state! {
+            static ALGO_INSTANCE: $crate::AlgoInstance<$algo> = $crate::AlgoInstance::new();
+        }
+
+        const _: () = assert!($size % $page_size == 0, "flash_size must be a multiple of page_size");
+        $(const _: () = assert!($page_buffers > 0, "page_buffers must be at least 1");)?
+        $(const _: () = assert!($init_retries > 0, "init_retries must be at least 1");)?
+        $(const _: &str = $entry_section;)?
+        $(
+            const _: () = assert!(
+                $page_size % $crate::count!($($empty_pattern)+) as u32 == 0,
+                "page_size must be a multiple of the empty_pattern length",
+            );
+        )?
+        $(
+            const _: () = assert!(
+                matches!($program_granularity, 1 | 2 | 4),
+                "program_granularity must be 1, 2 or 4",
+            );
+            const _: () = assert!(
+                $page_size % $program_granularity == 0,
+                "page_size must be a multiple of program_granularity",
+            );
+        )?
+        $(
+            const _: () = assert!(
+                $data_page_size > 0 && $data_size % $data_page_size == 0,
+                "data_region size must be a non-zero multiple of its page_size",
+            );
+            const _: () = assert!(
+                $data_address as u64 >= $addr as u64 + $size as u64
+                    || $data_address as u64 + $data_size as u64 <= $addr as u64,
+                "data_region overlaps the main flash",
+            );
+            $(
+                const _: () = assert!(
+                    matches!($data_granularity, 1 | 2 | 4)
+                        && $data_page_size % $data_granularity == 0,
+                    "data_region program_granularity must be 1, 2 or 4 and divide its page_size",
+                );
+            )?
+        )?
+        const PAGE_BUFFERS: u32 = $crate::or_default!($($page_buffers)?, 1);
+        const RAM_BUFFER_SIZE: u32 = $crate::or_default!($($ram_buffer_size)?, $page_size * PAGE_BUFFERS);
+        #[allow(dead_code)]
+        const TRANSFER_BUFFER: bool = $crate::or_default!($($transfer_buffer)?, false);
+        $(
+            const _: () = assert!(
+                !$transfer_buffer
+                    || (RAM_BUFFER_SIZE % PAGE_BUFFERS == 0 && RAM_BUFFER_SIZE / PAGE_BUFFERS >= $page_size),
+                "transfer_buffer needs ram_buffer_size to split into page_buffers slots of at least page_size",
+            );
+
+            $crate::export! {
+                #[allow(non_upper_case_globals)]
+                #[used]
+                #[link_section = "PrgDataBuffer"]
+                pub static FlashAlgorithmBuffer: $crate::TransferBuffer<
+                    { if $transfer_buffer { RAM_BUFFER_SIZE as usize } else { 0 } },
+                > = $crate::TransferBuffer::new();
+            }
+        )?
+        $(
+            const _: () = assert!($write_chunk > 0, "write_chunk must not be zero");
+            const _: () = assert!(
+                $write_chunk == 0 || $page_size % $write_chunk == 0,
+                "write_chunk must divide page_size",
+            );
+        )?
+        $($crate::ecc_size!(@check $ecc_size);)?
+        #[allow(dead_code)]
+        const PROGRAM_GRANULARITY: u32 = $crate::or_default!($($program_granularity)?, 1);
+        #[allow(dead_code)]
+        const BUFFER_ALIGNMENT: u32 = $crate::or_default!($($buffer_alignment)?, 1);
+        const _: () = assert!(
+            BUFFER_ALIGNMENT.is_power_of_two(),
+            "buffer_alignment must be a power of two"
+        );
+        #[allow(dead_code)]
+        const PROTECTED_SECTORS: &[u32] = &[$($($protected),*)?];
+        $($(
+            const _: () = assert!(
+                matches!($crate::sector_aligned_base::<$algo>($protected), Some(base) if base == $protected),
+                concat!("protected sector ", stringify!($protected), " is not the base of a sector"),
+            );
+        )*)?
+        $(const _: &str = $device_section;)?
+        $(
+            const _: () = assert!(
+                $sector_size % $page_size == 0,
+                concat!(
+                    "page_size does not divide the size of the sector at address ",
+                    stringify!($sector_addr),
+                ),
+            );
+            const _: () = assert!(
+                $sector_addr as u64 + $sector_size as u64 <= $size as u64,
+                concat!(
+                    "the sector at address ",
+                    stringify!($sector_addr),
+                    " extends past flash_size",
+                ),
+            );
+            const _: () = assert!(
+                $crate::sector_ends_before_next(<$algo as $crate::FlashGeometry>::SECTORS, $sector_addr),
+                concat!(
+                    "the sector at address ",
+                    stringify!($sector_addr),
+                    " overlaps or does not precede the next sector entry",
+                ),
+            );
+        )+
+
+        $(
+            const _: () = assert!($log_buffer > 0, "log_buffer must not be zero");
+            $crate::state! {
+                export
+                #[allow(non_upper_case_globals)]
+                pub static FlashAlgorithmLog: $crate::LogBuffer<{ $log_buffer }> = $crate::LogBuffer::new();
+            }
+        )?
+
+        $crate::export! {
+            #[allow(non_upper_case_globals)]
+            #[used]
+            #[link_section = "PrgDataStack"]
+            static FlashAlgorithmStack: $crate::Stack<{ $crate::or_default!($($stack_size)?, 512) }> =
+                $crate::Stack::new();
+        }
+
+        /// Returns the operation passed to the running `initialize`, or `None` when the
+        /// algorithm is not initialized.
+        #[allow(dead_code)]
+        pub fn current_operation() -> Option<$crate::Operation> {
+            if INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                // The operation is only written by `initialize`, before the flag is set.
+                Some(unsafe { ALGO_INSTANCE.operation() })
+            } else {
+                None
+            }
+        }
+
+        $crate::host_api!();
+
+        $crate::entry! {
+            [$($entry_section)?]
+            pub unsafe extern "C" fn initialize(addr: u32, clock: u32, op: u32) -> u32 {
+                if INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                    deinitialize();
+                }
+                $crate::busy_guard!();
+                let op = match <$crate::Operation as core::convert::TryFrom<u32>>::try_from(op) {
+                    Ok(op) => op,
+                    Err(e) => return e.get(),
+                };
+                $(
+                    if !($clock_min..=$clock_max).contains(&clock) {
+                        return $crate::error_codes::CLOCK_OUT_OF_RANGE.get();
+                    }
+                )?
+                let attempts: u32 = $crate::or_default!($($init_retries)?, 1);
+                let mut attempt = 1;
+                loop {
+                    match <$algo as FlashOps>::create(addr, clock, op) {
+                        Ok(instance) => {
+                            $crate::device_id_check!($algo, instance, op, [$($($allowed_id),+)?]);
+                            ALGO_INSTANCE.as_mut_ptr().write(instance);
+                            ALGO_INSTANCE.set_operation(op);
+                            INIT_FLAG.store(true, core::sync::atomic::Ordering::Relaxed);
+                            return 0;
+                        }
+                        Err(e) if attempt >= attempts => return e.get(),
+                        Err(_) => {
+                            <$algo as FlashOps>::retry_delay(attempt);
+                            attempt += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        $crate::entry! {
+            [$($entry_section)?]
+            pub unsafe extern "C" fn deinitialize() -> u32 {
+                if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                    return $crate::error_codes::NOT_INITIALIZED.get();
+                }
+                $crate::busy_guard!();
+                let lock = ALGO_INSTANCE.relock();
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                let uninit = <$algo as FlashOps>::uninit(instance, ALGO_INSTANCE.operation());
+                let deinit = <$algo as FlashOps>::deinit(instance);
+                let result = lock.and(uninit).and(deinit);
+                ALGO_INSTANCE.as_mut_ptr().drop_in_place();
+                INIT_FLAG.store(false, core::sync::atomic::Ordering::Relaxed);
+                match result {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+
+        $crate::entry! {
+            [$($entry_section)?]
+            pub unsafe extern "C" fn finalize() -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::busy_guard!();
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                match <$algo as FlashOps>::finalize(instance) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+
+        $crate::entry! {
+            [$($entry_section)?]
+            pub unsafe extern "C" fn erase_sector(addr: u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::busy_guard!();
+                if let Some(region) = <$algo as $crate::FlashGeometry>::DATA_REGION {
+                    if region.contains(addr, 1) {
+                        $crate::erase_alignment_check!($algo, addr);
+                        let instance = $crate::unlocked_instance!();
+                        return match <$algo as FlashOps>::erase_data(instance, addr) {
+                            Ok(()) => 0,
+                            Err(e) => e.get(),
+                        };
+                    }
+                }
+                $crate::bounds_check!($algo, addr, 1);
+                $crate::erase_alignment_check!($algo, addr);
+                $crate::protected_check!($algo, addr, 1);
+                let instance = $crate::unlocked_instance!();
+                match <$algo as FlashOps>::erase_sector(instance, addr) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+
+        $crate::entry! {
+            [$($entry_section)?]
+            pub unsafe extern "C" fn program_page(addr: u32, size: u32, data: *const u8) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if size == 0 {
+                        return 0;
+                    }
+                    if TRANSFER_BUFFER && size > RAM_BUFFER_SIZE / PAGE_BUFFERS {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                    if !(data as usize).is_multiple_of(BUFFER_ALIGNMENT as usize) {
+                        return $crate::error_codes::MISALIGNED_BUFFER.get();
+                    }
+                }
+                $crate::busy_guard!();
+                let data_slice: &[u8] = core::slice::from_raw_parts(data, size as usize);
+                if let Some(region) = <$algo as $crate::FlashGeometry>::DATA_REGION {
+                    if region.contains(addr, size) {
+                        $crate::checks! {
+                            if !size.is_multiple_of(region.program_granularity)
+                                || (addr - region.address) % region.page_size + size > region.page_size
+                            {
+                                return $crate::error_codes::UNALIGNED_PROGRAM.get();
+                            }
+                        }
+                        $crate::program_alignment_check!($algo, addr);
+                        let instance = $crate::unlocked_instance!();
+                        return match <$algo as FlashOps>::program_data(instance, addr, data_slice) {
+                            Ok(()) => 0,
+                            Err(e) => e.get(),
+                        };
+                    }
+                }
+                $crate::checks! {
+                    if !size.is_multiple_of(PROGRAM_GRANULARITY) {
+                        return $crate::error_codes::UNALIGNED_PROGRAM.get();
+                    }
+                }
+                $crate::bounds_check!($algo, addr, size);
+                $crate::program_alignment_check!($algo, addr);
+                $crate::protected_check!($algo, addr, size);
+                let instance = $crate::unlocked_instance!();
+                let result = if $crate::or_default!($($pad_pages)?, false) {
+                    $crate::program_padded::<$algo, { $page_size as usize }>(instance, addr, data_slice)
+                } else {
+                    $crate::program_main::<$algo>(instance, addr, data_slice)
+                };
+                match result {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+
+        $crate::erase_chip!($algo, [$($entry_section)?]);
+        $crate::erase_chip_step!($algo, [$($entry_section)?]);
+        $crate::verify!($algo, [$($entry_section)?]);
+        $crate::read!($algo, [$($entry_section)?]);
+        $crate::blank_check!($algo, [$($entry_section)?]);
+        $crate::erase_range!($algo, [$($entry_section)?]);
+        $crate::checksum!($algo, [$($entry_section)?]);
+        $crate::verify_crc!($algo, [$($entry_section)?]);
+        $crate::verify_pages!($algo, [$($entry_section)?]);
+        $crate::read_device_id!($algo, [$($entry_section)?]);
+        $crate::write_config!($algo, [$($entry_section)?]);
+        $crate::program_pages!($algo, [$($entry_section)?]);
+        $crate::program_and_verify!($algo, [$($entry_section)?]);
+        $crate::program_page_counted!($algo, [$($entry_section)?]);
+        $crate::erase_if_needed!($algo, [$($entry_section)?]);
+        $crate::estimated_duration!($algo, [$($entry_section)?]);
+        $crate::sector_size!($algo, [$($entry_section)?]);
+
+        impl $crate::FlashGeometry for $algo {
+            const FLASH_ADDRESS: u32 = $addr;
+            const FLASH_SIZE: u32 = $size;
+            const PAGE_SIZE: u32 = $page_size;
+            const EMPTY_VALUE: u8 = $empty;
+            const PROGRAM_TIMEOUT: u32 = $crate::or_default!($($crate::timeout($program_timeout))?, 1000);
+            const ERASE_TIMEOUT: u32 = $crate::or_default!($($crate::timeout($erase_timeout))?, 2000);
+            const SECTORS: &'static [(u32, u32)] = &[$(($sector_size, $sector_addr)),+];
+            $(const EMPTY_PATTERN: &'static [u8] = &[$($empty_pattern),+];)?
+            $(const WRITE_CHUNK: u32 = $write_chunk;)?
+            $(const WORD_ORDER: $crate::WordOrder = $word_order;)?
+            $(
+                const DATA_REGION: Option<$crate::DataRegion> = Some($crate::DataRegion {
+                    address: $data_address,
+                    size: $data_size,
+                    page_size: $data_page_size,
+                    program_granularity: $crate::or_default!($($data_granularity)?, 1),
+                });
+            )?
+            $(const REGIONS: &'static [(u32, u32)] = &[$(($region_start, $region_len)),+];)?
+            $crate::ecc_size!($($ecc_size)?);
+            $crate::erase_progress!(@geometry);
+        }
+
+        $crate::erase_progress!();
+
+        pub const FLASH_PROPERTIES: $crate::FlashProperties = $crate::FlashProperties::of::<$algo>();
+
+        /// Number of sector entries, not counting the terminator of `FlashDeviceInfo`.
+        pub const SECTOR_COUNT: usize = $crate::count!($($sector_size)*);
+
+        /// The sector entries of `FlashDeviceInfo` without its terminator.
+        pub const SECTORS: [Sector; SECTOR_COUNT] = [$(Sector { size: $sector_size, address: $sector_addr }),+];
+
+        /// Page program timeout in milliseconds of each entry of `SECTORS`, falling back to
+        /// `program_timeout` for entries that declare none.
+        pub const SECTOR_PROGRAM_TIMEOUTS: [u32; SECTOR_COUNT] = [$(
+            $crate::or_default!(
+                $($crate::timeout($sector_timeout))?,
+                <$algo as $crate::FlashGeometry>::PROGRAM_TIMEOUT
+            )
+        ),+];
+
+        /// The longest of `SECTOR_PROGRAM_TIMEOUTS`.
+        pub const MAX_PROGRAM_TIMEOUT: u32 = $crate::max_timeout(&SECTOR_PROGRAM_TIMEOUTS);
+
+        $crate::device_info! {
+            [$($device_section)?]
+            #[allow(non_upper_case_globals)]
+            #[used]
+            pub static FlashSectorTimeouts: [u32; SECTOR_COUNT] = SECTOR_PROGRAM_TIMEOUTS;
+        }
+
+        const SENTINEL_COUNT: usize = $crate::or_default!($($crate::sector_sentinel!(@count $sentinel))?, 1);
+
+        /// The terminator appended to the sector table of `FlashDeviceInfo`, if any.
+        pub const SENTINEL: [Sector; SENTINEL_COUNT] = $crate::or_default!(
+            $($crate::sector_sentinel!(@entries $sentinel))?,
+            [Sector { size: 0xffff_ffff, address: 0xffff_ffff }]
+        );
+
+        const _: () = assert!(
+            SENTINEL_COUNT == 0 || SENTINEL[0].size == 0 || SENTINEL[0].address >= $size,
+            "sector_sentinel must not describe a sector of the flash",
+        );
+
+        const DEVICE_ADDRESS: u32 = $crate::or_default!($($device_address)?, $addr);
+
+        /// Address the descriptors' addresses are relative to: `0`, or `device_address` for an
+        /// algorithm defined with `flash_algorithm_for_region!`, which exports it as
+        /// `FlashLoadBase`.
+        pub const LOAD_BASE: u32 = $crate::load_base!(@value [$($relocatable)?], DEVICE_ADDRESS);
+
+        $crate::load_base!([$($relocatable)?], [$($device_section)?]);
+
+        $crate::device_info! {
+            [$($device_section)?]
+            #[allow(non_upper_case_globals)]
+            #[used]
+            pub static FlashDeviceInfo: FlashDevice = FlashDevice {
+                vers: $crate::or_default!($($version)?, 0x0),
+                dev_name: $crate::or_default!($($crate::device_name($name))?, [0u8; 128]),
+                dev_type: $crate::or_default!($($dev_type as u16)?, 5),
+                dev_addr: DEVICE_ADDRESS - LOAD_BASE,
+                device_size: $size,
+                page_size: $page_size,
+                _reserved: 0,
+                empty: $empty,
+                program_time_out: $crate::or_default!($($crate::timeout($program_timeout))?, 1000),
+                erase_time_out: $crate::or_default!($($crate::timeout($erase_timeout))?, 2000),
+                flash_sectors: SECTORS,
+                sentinel: SENTINEL,
+            };
+        }
+
+        /// `descriptor_crc` of `FlashDeviceInfo`, exported as `FlashDescriptorCrc`.
+        pub const DESCRIPTOR_CRC: u32 = $crate::descriptor_crc(
+            DEVICE_ADDRESS - LOAD_BASE,
+            $size,
+            $page_size,
+            <$algo as $crate::FlashGeometry>::SECTORS,
+        );
+
+        $crate::device_info! {
+            [$($device_section)?]
+            #[allow(non_upper_case_globals)]
+            #[used]
+            pub static FlashDescriptorCrc: u32 = DESCRIPTOR_CRC;
+        }
+
+        #[repr(C)]
+        pub struct FlashDevice {
+            vers: u16,
+            dev_name: [u8; 128],
+            dev_type: u16,
+            dev_addr: u32,
+            device_size: u32,
+            page_size: u32,
+            _reserved: u32,
+            empty: u8,
+            program_time_out: u32,
+            erase_time_out: u32,
+            flash_sectors: [Sector; SECTOR_COUNT],
+            sentinel: [Sector; SENTINEL_COUNT],
+        }
+
+        $crate::descriptor_v2! {
+            #[repr(C)]
+            pub struct FlashDeviceV2 {
+                vers: u16,
+                dev_name: [u8; 128],
+                dev_type: u16,
+                dev_addr: u32,
+                device_size: u32,
+                page_size: u32,
+                _reserved: u32,
+                empty: u8,
+                program_time_out: u32,
+                erase_time_out: u32,
+                algo_version: u32,
+                clock_min: u32,
+                clock_max: u32,
+                program_granularity: u32,
+                flash_sectors: [Sector; SECTOR_COUNT],
+                sentinel: [Sector; SENTINEL_COUNT],
+            }
+
+            $crate::export! {
+                #[allow(non_upper_case_globals)]
+                #[used]
+                #[link_section = "DeviceDataV2"]
+                pub static FlashDeviceInfoV2: FlashDeviceV2 = FlashDeviceV2 {
+                    vers: $crate::FLASH_DEVICE_V2_VERS,
+                    dev_name: $crate::or_default!($($crate::device_name($name))?, [0u8; 128]),
+                    dev_type: $crate::or_default!($($dev_type as u16)?, 5),
+                    dev_addr: DEVICE_ADDRESS - LOAD_BASE,
+                    device_size: $size,
+                    page_size: $page_size,
+                    _reserved: 0,
+                    empty: $empty,
+                    program_time_out: $crate::or_default!($($crate::timeout($program_timeout))?, 1000),
+                    erase_time_out: $crate::or_default!($($crate::timeout($erase_timeout))?, 2000),
+                    algo_version: $crate::or_default!($($version)?, 0x0) as u32,
+                    clock_min: $crate::or_default!($($clock_min)?, 0),
+                    clock_max: $crate::or_default!($($clock_max)?, u32::MAX),
+                    program_granularity: PROGRAM_GRANULARITY,
+                    flash_sectors: SECTORS,
+                    sentinel: SENTINEL,
+                };
+            }
+        }
+
+        #[repr(C)]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        pub struct Sector {
+            pub size: u32,
+            pub address: u32,
+        }
+
+        /// The `flags` word of `FlashAlgorithmInfo`.
+        pub const ALGORITHM_FLAGS: u32 = if $crate::or_default!($($requires_cache_disable)?, false) {
+            $crate::ALGORITHM_FLAG_CACHE_DISABLE
+        } else {
+            0
+        };
+
+        $crate::device_info! {
+            [$($device_section)?]
+            #[allow(non_upper_case_globals)]
+            #[used]
+            pub static FlashAlgorithmInfo: AlgorithmInfo = AlgorithmInfo {
+                magic: $crate::ALGORITHM_INFO_MAGIC,
+                version: $crate::ALGORITHM_INFO_VERSION,
+                flash_address: <$algo as $crate::FlashGeometry>::FLASH_ADDRESS.wrapping_sub(LOAD_BASE),
+                flash_size: $size,
+                page_size: $page_size,
+                empty_value: $empty as u32,
+                ram_buffer_size: RAM_BUFFER_SIZE,
+                page_buffers: PAGE_BUFFERS,
+                program_granularity: $crate::or_default!($($program_granularity)?, 1),
+                flags: ALGORITHM_FLAGS,
+                buffer_alignment: BUFFER_ALIGNMENT,
+                sector_count: SECTOR_COUNT as u32,
+                sectors: SECTORS,
+            };
+        }
+
+        #[repr(C)]
+        pub struct AlgorithmInfo {
+            magic: u32,
+            version: u32,
+            flash_address: u32,
+            flash_size: u32,
+            page_size: u32,
+            empty_value: u32,
+            ram_buffer_size: u32,
+            page_buffers: u32,
+            program_granularity: u32,
+            flags: u32,
+            buffer_alignment: u32,
+            sector_count: u32,
+            sectors: [Sector; SECTOR_COUNT],
+        }
+    };
+    ($algo:ty, {flash_address: $addr:expr, flash_size: $size:expr, page_size: $page_size:expr, empty_value: $empty:expr, sectors: [] $(, $($rest:tt)*)?}) => {
+        compile_error!("flash_algorithm! needs at least one sector");
+    };
+    ($algo:ty, {flash_address: $addr:expr, flash_size: $size:expr, page_size: $page_size:expr, empty_value: $empty:expr, sectors: uniform {size: $sector_size:expr, count: $sector_count:expr $(, start: $start:expr)?} $(, $($rest:tt)*)?}) => {
+        const _: () = assert!($sector_count > 0, "uniform sectors need a non-zero count");
+        const _: () = assert!(
+            $crate::or_default!($($start)?, $addr) >= $addr,
+            "uniform sectors start below flash_address"
+        );
+        const _: () = assert!(
+            $sector_count == 0
+                || u32::saturating_sub($crate::or_default!($($start)?, $addr), $addr) as u64
+                    + $sector_size as u64 * $sector_count as u64
+                    == $size as u64,
+            "uniform sectors must end at the end of the flash: start + size * count == flash_address + flash_size"
+        );
+        $crate::flash_algorithm!($algo, {
+            flash_address: $addr,
+            flash_size: $size,
+            page_size: $page_size,
+            empty_value: $empty,
+            sectors: [{size: $sector_size, address: u32::saturating_sub($crate::or_default!($($start)?, $addr), $addr)}]
+            $(, $($rest)*)?
+        });
+    };
+    ($algo:ty, {regions: [{address: $first_addr:expr, size: $first_size:expr, sectors: [$({size: $first_sector_size:expr, address: $first_sector_addr:expr}),+]} $(, {address: $region_addr:expr, size: $region_size:expr, sectors: [$({size: $sector_size:expr, address: $sector_addr:expr}),+]})*], page_size: $page_size:expr, empty_value: $empty:expr $(, $($rest:tt)*)?}) => {
+        const _: () = assert!(
+            $crate::regions_ascending(&[($first_addr, $first_size) $(, ($region_addr, $region_size))*]),
+            "regions must be listed in ascending address order without overlapping"
+        );
+        $(
+            const _: () = assert!(
+                $first_sector_addr < $first_size,
+                concat!("the sector at address ", stringify!($first_sector_addr), " lies outside its region"),
+            );
+            const _: () = assert!(
+                $first_sector_addr as u64 + $first_sector_size as u64 <= $first_size as u64,
+                concat!("the sector at address ", stringify!($first_sector_addr), " extends past its region"),
+            );
+        )+
+        $($(
+            const _: () = assert!(
+                $sector_addr < $region_size,
+                concat!("the sector at address ", stringify!($sector_addr), " lies outside its region"),
+            );
+            const _: () = assert!(
+                $sector_addr as u64 + $sector_size as u64 <= $region_size as u64,
+                concat!("the sector at address ", stringify!($sector_addr), " extends past its region"),
+            );
+        )+)*
+        $crate::flash_algorithm!($algo, {
+            flash_address: $first_addr,
+            flash_size: $crate::regions_size(&[($first_addr, $first_size) $(, ($region_addr, $region_size))*]),
+            page_size: $page_size,
+            empty_value: $empty,
+            sectors: [
+                $({size: $first_sector_size, address: $first_sector_addr}),+
+                $($(, {size: $sector_size, address: $region_addr - $first_addr + $sector_addr})+)*
+            ]
+            $(, $($rest)*)?,
+            @{regions: [($first_addr, $first_size) $(, ($region_addr, $region_size))*]}
+        });
+    };
+}
+
+/// [`flash_algorithm!`] for position-independent loaders that place the flash at a base only
+/// known to the host. It takes the same keys, and emits every address in the descriptors as
+/// an offset from that base.
+///
+/// `dev_addr` in `FlashDeviceInfo` and `FlashDeviceInfoV2` becomes `0`, the sector table
+/// keeps its usual offsets, and `flash_address` in `FlashAlgorithmInfo` and the address hashed
+/// into `DESCRIPTOR_CRC` are made relative in the same way. The base the algorithm was built
+/// for, `device_address` or else `flash_address`, is exported as the `u32` symbol
+/// `FlashLoadBase` next to the descriptors, so all of them stay free of absolute literals.
+///
+/// The host is responsible for adding the base: it reads `FlashLoadBase`, or substitutes
+/// the base it resolved, adds it to `dev_addr` and to each sector address, and passes the
+/// resulting absolute addresses to the entry points. The algorithm itself still sees
+/// [`FlashGeometry::FLASH_ADDRESS`] as declared, so a host relocating the flash elsewhere must
+/// not enable `bounds-check` or other address validation in the algorithm it loads.
+///
+/// ```ignore
+/// flash_algorithm_for_region!(Algo, {
+///     flash_address: 0x9000_0000,
+///     flash_size: 0x100_0000,
+///     page_size: 0x100,
+///     empty_value: 0xFF,
+///     sectors: [{size: 0x1000, address: 0x0}]
+/// });
+/// ```
+#[macro_export]
+macro_rules! flash_algorithm_for_region {
+    ($algo:ty, { $($keys:tt)+ }) => {
+        $crate::flash_algorithm!($algo, { $($keys)+, @relocatable });
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! load_base {
+    (@value [], $base:expr) => {
+        0
+    };
+    (@value [relocatable], $base:expr) => {
+        $base
+    };
+    ([], [$($section:literal)?]) => {};
+    ([relocatable], [$($section:literal)?]) => {
+        $crate::device_info! {
+            [$($section)?]
+            #[allow(non_upper_case_globals)]
+            #[used]
+            pub static FlashLoadBase: u32 = LOAD_BASE;
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "cmsis-sections", feature = "trustzone-secure")))]
+macro_rules! entry {
+    ([$section:literal] $item:item) => {
+        $crate::export! {
+            #[link_section = $section]
+            $item
+        }
+    };
+    ([] $item:item) => {
+        $crate::export! {
+            #[cfg_attr(not(target_arch = "riscv32"), link_section = ".entry")]
+            #[cfg_attr(target_arch = "riscv32", link_section = ".text.entry")]
+            $item
+        }
+    };
+    (as $name:literal [$section:literal] $item:item) => {
+        $crate::export! {
+            $name
+            #[link_section = $section]
+            $item
+        }
+    };
+    (as $name:literal [] $item:item) => {
+        $crate::export! {
+            $name
+            #[cfg_attr(not(target_arch = "riscv32"), link_section = ".entry")]
+            #[cfg_attr(target_arch = "riscv32", link_section = ".text.entry")]
+            $item
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(feature = "trustzone-secure", not(feature = "cmsis-sections")))]
+macro_rules! entry {
+    ([$section:literal] $item:item) => {
+        $crate::export! {
+            #[link_section = $section]
+            $item
+        }
+    };
+    ([] $item:item) => {
+        $crate::export! {
+            #[link_section = ".entry.secure"]
+            $item
+        }
+    };
+    (as $name:literal [$section:literal] $item:item) => {
+        $crate::export! {
+            $name
+            #[link_section = $section]
+            $item
+        }
+    };
+    (as $name:literal [] $item:item) => {
+        $crate::export! {
+            $name
+            #[link_section = ".entry.secure"]
+            $item
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "cmsis-sections")]
+macro_rules! entry {
+    ([$section:literal] $item:item) => {
+        $crate::export! {
+            #[link_section = $section]
+            $item
+        }
+    };
+    ([] $item:item) => {
+        $crate::export! {
+            #[link_section = "PrgCode"]
+            $item
+        }
+    };
+    (as $name:literal [$section:literal] $item:item) => {
+        $crate::export! {
+            $name
+            #[link_section = $section]
+            $item
+        }
+    };
+    (as $name:literal [] $item:item) => {
+        $crate::export! {
+            $name
+            #[link_section = "PrgCode"]
+            $item
+        }
+    };
+}
+
+/// Exports an entry point or symbol the host looks up, under its own name or under a CMSIS
+/// name that differs from its Rust one, such as `flash_read` exported as `read`. Under `std`
+/// the symbols stay mangled, so that linking algorithms into a host test binary neither
+/// replaces the C library function of the same name nor clashes between invocations.
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "std"))]
+macro_rules! export {
+    ($name:literal $item:item) => {
+        #[export_name = $name]
+        $item
+    };
+    ($item:item) => {
+        #[no_mangle]
+        $item
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "std")]
+macro_rules! export {
+    ($name:literal $item:item) => {
+        $item
+    };
+    ($item:item) => {
+        $item
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "cmsis-sections"))]
+macro_rules! state {
+    (export $item:item) => {
+        $crate::export! {
+            $item
+        }
+    };
+    ($item:item) => {
+        $item
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "cmsis-sections")]
+macro_rules! state {
+    (export $item:item) => {
+        $crate::export! {
+            #[link_section = "PrgData"]
+            $item
+        }
+    };
+    ($item:item) => {
+        #[link_section = "PrgData"]
+        $item
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "cmsis-sections"))]
+macro_rules! device_info {
+    ([$section:literal] $item:item) => {
+        $crate::export! {
+            #[link_section = $section]
+            $item
+        }
+    };
+    ([] $item:item) => {
+        $crate::export! {
+            #[link_section = "DeviceData"]
+            $item
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "cmsis-sections")]
+macro_rules! device_info {
+    ([$section:literal] $item:item) => {
+        $crate::export! {
+            #[link_section = $section]
+            $item
+        }
+    };
+    ([] $item:item) => {
+        $crate::export! {
+            #[link_section = "DevDscr"]
+            $item
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(
+    not(feature = "erase-chip"),
+    feature = "no-unsupported-stubs",
+    not(feature = "full-symbol-table")
+))]
+macro_rules! erase_chip {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(
+    not(feature = "erase-chip"),
+    any(not(feature = "no-unsupported-stubs"), feature = "full-symbol-table")
+))]
+macro_rules! erase_chip {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn erase_chip() -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `erase_chip`.
+            fn __erase_chip() -> u32 {
+                erase_chip()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "erase-chip")]
+macro_rules! erase_chip {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn erase_chip() -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::checks! {
+                    if !PROTECTED_SECTORS.is_empty() {
+                        return $crate::error_codes::SECTOR_PROTECTED.get();
+                    }
+                }
+                let instance = $crate::unlocked_instance!();
+                $crate::reset_erase_progress::<$type>();
+                match <$type as FlashOps>::erase_chip(instance) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `erase_chip`.
+            fn __erase_chip() -> u32 {
+                erase_chip()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "erase-step", feature = "full-symbol-table")))]
+macro_rules! erase_chip_step {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "erase-step"), feature = "full-symbol-table"))]
+macro_rules! erase_chip_step {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn erase_chip_step(_done: *mut u32) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "erase-step")]
+macro_rules! erase_chip_step {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn erase_chip_step(done: *mut u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if done.is_null() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::checks! {
+                    if !PROTECTED_SECTORS.is_empty() {
+                        return $crate::error_codes::SECTOR_PROTECTED.get();
+                    }
+                }
+                let instance = $crate::unlocked_instance!();
+                let step = ALGO_INSTANCE.step();
+                if step == 0 {
+                    $crate::reset_erase_progress::<$type>();
+                }
+                match <$type as FlashOps>::erase_chip_step(instance, step) {
+                    Ok($crate::StepResult::InProgress) => {
+                        ALGO_INSTANCE.set_step(step + 1);
+                        done.write(0);
+                        0
+                    }
+                    Ok($crate::StepResult::Done) => {
+                        ALGO_INSTANCE.set_step(0);
+                        done.write(1);
+                        0
+                    }
+                    Err(e) => {
+                        ALGO_INSTANCE.set_step(0);
+                        e.get()
+                    }
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(
+    not(feature = "verify"),
+    feature = "no-unsupported-stubs",
+    not(feature = "full-symbol-table")
+))]
+macro_rules! verify {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(
+    not(feature = "verify"),
+    any(not(feature = "no-unsupported-stubs"), feature = "full-symbol-table")
+))]
+macro_rules! verify {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn verify(_addr: u32, _size: u32, _data: *const u8) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `verify`, comparing the flash against `data`.
+            fn __verify(addr: u32, data: &[u8]) -> u32 {
+                verify(addr, data.len() as u32, data.as_ptr())
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "verify")]
+macro_rules! verify {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn verify(addr: u32, size: u32, data: *const u8) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if size == 0 {
+                        return 0;
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::checks! {
+                    if addr.checked_add(size - 1).is_none() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                    if !data.is_null() && (data as usize).checked_add(size as usize).is_none() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                }
+                $crate::bounds_check!($type, addr, size);
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                let data_slice = if data.is_null() {
+                    None
+                } else {
+                    Some(unsafe { core::slice::from_raw_parts(data, size as usize) })
+                };
+                match <$type as FlashOps>::verify(instance, addr, size, data_slice) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `verify`, comparing the flash against `data`.
+            fn __verify(addr: u32, data: &[u8]) -> u32 {
+                verify(addr, data.len() as u32, data.as_ptr())
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "read", feature = "full-symbol-table")))]
+macro_rules! read {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "read"), feature = "full-symbol-table"))]
+macro_rules! read {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            as "read" [$($section)?]
+            pub unsafe extern "C" fn flash_read(_addr: u32, _size: u32, _data: *mut u8) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `read`, filling `data`.
+            fn __read(addr: u32, data: &mut [u8]) -> u32 {
+                flash_read(addr, data.len() as u32, data.as_mut_ptr())
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "read")]
+macro_rules! read {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            as "read" [$($section)?]
+            pub unsafe extern "C" fn flash_read(addr: u32, size: u32, data: *mut u8) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if data.is_null() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::bounds_check!($type, addr, size);
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                let data_slice: &mut [u8] = core::slice::from_raw_parts_mut(data, size as usize);
+                match <$type as FlashOps>::read(instance, addr, data_slice) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `read`, filling `data`.
+            fn __read(addr: u32, data: &mut [u8]) -> u32 {
+                flash_read(addr, data.len() as u32, data.as_mut_ptr())
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "blank-check", feature = "full-symbol-table")))]
+macro_rules! blank_check {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "blank-check"), feature = "full-symbol-table"))]
+macro_rules! blank_check {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn blank_check(_addr: u32, _size: u32) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `blank_check`.
+            fn __blank_check(addr: u32, size: u32) -> u32 {
+                blank_check(addr, size)
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "blank-check")]
+macro_rules! blank_check {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn blank_check(addr: u32, size: u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::bounds_check!($type, addr, size);
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                match <$type as FlashOps>::blank_check(instance, addr, size) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `blank_check`.
+            fn __blank_check(addr: u32, size: u32) -> u32 {
+                blank_check(addr, size)
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "erase-range", feature = "full-symbol-table")))]
+macro_rules! erase_range {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "erase-range"), feature = "full-symbol-table"))]
+macro_rules! erase_range {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn erase_range(_addr: u32, _len: u32) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "erase-range")]
+macro_rules! erase_range {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn erase_range(addr: u32, len: u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if len == 0 {
+                        return 0;
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::bounds_check!($type, addr, len);
+                $crate::protected_check!($type, addr, len);
+                let instance = $crate::unlocked_instance!();
+                $crate::reset_erase_progress::<$type>();
+                match <$type as FlashOps>::erase_range(instance, addr, len) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "checksum", feature = "full-symbol-table")))]
+macro_rules! checksum {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "checksum"), feature = "full-symbol-table"))]
+macro_rules! checksum {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn checksum(_addr: u32, _size: u32, _out: *mut u32) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "checksum")]
+macro_rules! checksum {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn checksum(addr: u32, size: u32, out: *mut u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if out.is_null() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::bounds_check!($type, addr, size);
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                match <$type as FlashOps>::checksum(instance, addr, size) {
+                    Ok(crc) => {
+                        out.write(crc);
+                        0
+                    }
+                    Err(e) => e.get(),
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "verify-crc", feature = "full-symbol-table")))]
+macro_rules! verify_crc {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "verify-crc"), feature = "full-symbol-table"))]
+macro_rules! verify_crc {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn verify_crc(_addr: u32, _size: u32, _crc: u32) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "verify-crc")]
+macro_rules! verify_crc {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn verify_crc(addr: u32, size: u32, crc: u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::bounds_check!($type, addr, size);
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                match <$type as FlashOps>::verify_crc(instance, addr, size, crc) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "verify-pages", feature = "full-symbol-table")))]
+macro_rules! verify_pages {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "verify-pages"), feature = "full-symbol-table"))]
+macro_rules! verify_pages {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn verify_pages(
+                _addr: u32,
+                _count: u32,
+                _crcs: *const u32,
+                _failed: *mut u32,
+            ) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "verify-pages")]
+macro_rules! verify_pages {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn verify_pages(
+                addr: u32,
+                count: u32,
+                crcs: *const u32,
+                failed: *mut u32,
+            ) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if crcs.is_null() || failed.is_null() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                    if count == 0 {
+                        return 0;
+                    }
+                }
+                $crate::busy_guard!();
+                let size = count.wrapping_mul(<$type as $crate::FlashGeometry>::PAGE_SIZE);
+                $crate::checks! {
+                    if count
+                        .checked_mul(<$type as $crate::FlashGeometry>::PAGE_SIZE)
+                        .and_then(|size| addr.checked_add(size - 1))
+                        .is_none()
+                    {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                }
+                $crate::bounds_check!($type, addr, size);
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                let page_crcs = core::slice::from_raw_parts(crcs, count as usize);
+                match <$type as FlashOps>::verify_pages(instance, addr, page_crcs, &mut *failed) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "sector-size", feature = "full-symbol-table")))]
+macro_rules! sector_size {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "sector-size"), feature = "full-symbol-table"))]
+macro_rules! sector_size {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn sector_size(_addr: u32, _out: *mut u32) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "sector-size")]
+macro_rules! sector_size {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn sector_size(addr: u32, out: *mut u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if out.is_null() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                }
+                $crate::busy_guard!();
+                let in_data_region = match <$type as $crate::FlashGeometry>::DATA_REGION {
+                    Some(region) => region.contains(addr, 1),
+                    None => false,
+                };
+                if !in_data_region {
+                    $crate::bounds_check!($type, addr, 1);
+                }
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                match <$type as FlashOps>::sector_size_at(instance, addr) {
+                    Ok(size) => {
+                        out.write(size);
+                        0
+                    }
+                    Err(e) => e.get(),
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "write-config", feature = "full-symbol-table")))]
+macro_rules! write_config {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "write-config"), feature = "full-symbol-table"))]
+macro_rules! write_config {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn write_config(_reg: u32, _value: u32) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "write-config")]
+macro_rules! write_config {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn write_config(reg: u32, value: u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::busy_guard!();
+                let instance = $crate::unlocked_instance!();
+                match <$type as FlashOps>::write_config(instance, reg, value) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "device-id", feature = "full-symbol-table")))]
+macro_rules! read_device_id {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "device-id"), feature = "full-symbol-table"))]
+macro_rules! read_device_id {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn read_device_id(_out: *mut u32) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "device-id")]
+macro_rules! read_device_id {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn read_device_id(out: *mut u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if out.is_null() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                }
+                $crate::busy_guard!();
+                let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
+                match <$type as FlashOps>::read_device_id(instance) {
+                    Ok(id) => {
+                        out.write(id);
+                        0
+                    }
+                    Err(e) => e.get(),
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "std"))]
+macro_rules! host {
+    ($($item:tt)*) => {};
 }
 
-pub const ERASE: u32 = 1;
-pub const PROGRAM: u32 = 2;
-pub const VERIFY: u32 = 3;
+/// Defines the safe host-side form of an entry point, serialized by [`host_lock`] like the
+/// wrappers of [`host_api!`].
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "std")]
+macro_rules! host {
+    ($(#[doc = $doc:literal])* fn $name:ident($($arg:ident: $ty:ty),*) -> u32 { $($body:tt)* }) => {
+        $(#[doc = $doc])*
+        #[allow(dead_code)]
+        pub fn $name($($arg: $ty),*) -> u32 {
+            let _lock = $crate::host_lock();
+            unsafe { $($body)* }
+        }
+    };
+}
 
-pub type Error = core::num::NonZeroU32;
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "std"))]
+macro_rules! host_api {
+    () => {};
+}
 
-pub trait FlashOps {
-    fn create(address: u32, clock: u32, operation: Operation) -> Result<Self, Error>
-    where
-        Self: Sized;
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "std")]
+macro_rules! host_api {
+    () => {
+        /// Safe host-side form of `initialize`, for driving the state machine from tests.
+        #[allow(dead_code)]
+        pub fn __init(addr: u32, clock: u32, op: u32) -> u32 {
+            let _lock = $crate::host_lock();
+            unsafe { initialize(addr, clock, op) }
+        }
 
-    #[cfg(feature = "erase-chip")]
-    fn erase_chip(&mut self) -> Result<(), Error>;
+        /// Safe host-side form of `deinitialize`.
+        #[allow(dead_code)]
+        pub fn __deinit() -> u32 {
+            let _lock = $crate::host_lock();
+            unsafe { deinitialize() }
+        }
 
-    fn erase_sector(&mut self, address: u32) -> Result<(), Error>;
-    fn program_page(&mut self, address: u32, data: &[u8]) -> Result<(), Error>;
+        /// Safe host-side form of `erase_sector`.
+        #[allow(dead_code)]
+        pub fn __erase_sector(addr: u32) -> u32 {
+            let _lock = $crate::host_lock();
+            unsafe { erase_sector(addr) }
+        }
 
-    #[cfg(feature = "verify")]
-    fn verify(&mut self, address: u32, size: u32, data: Option<&[u8]>) -> Result<(), Error>;
+        /// Safe host-side form of `program_page`, taking the page as a slice.
+        #[allow(dead_code)]
+        pub fn __program_page(addr: u32, data: &[u8]) -> u32 {
+            let _lock = $crate::host_lock();
+            unsafe { program_page(addr, data.len() as u32, data.as_ptr()) }
+        }
+
+        /// Safe host-side form of `finalize`.
+        #[allow(dead_code)]
+        pub fn __finalize() -> u32 {
+            let _lock = $crate::host_lock();
+            unsafe { finalize() }
+        }
+    };
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub enum Operation {
-    Erase = 1,
-    Program = 2,
-    Verify = 3,
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "program-pages", feature = "full-symbol-table")))]
+macro_rules! program_pages {
+    ($type:ty, [$($section:literal)?]) => {};
 }
 
+#[doc(hidden)]
 #[macro_export]
-macro_rules! flash_algorithm {
-    ($algo:ty, {flash_address: $addr:expr, flash_size: $size:expr, page_size: $page_size:expr, empty_value: $empty:expr, sectors: [$({size: $sector_size:expr, address: $sector_addr:expr}),+]}) => {
-        static mut INIT_FLAG: bool = false;
-        static mut ALGO_INSTANCE: core::mem::MaybeUninit<$algo> = core::mem::MaybeUninit::uninit();
+#[cfg(all(not(feature = "program-pages"), feature = "full-symbol-table"))]
+macro_rules! program_pages {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn program_pages(
+                _addr: u32,
+                _size: u32,
+                _data: *const u8,
+            ) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `program_pages`, taking the pages as a slice.
+            fn __program_pages(addr: u32, data: &[u8]) -> u32 {
+                program_pages(addr, data.len() as u32, data.as_ptr())
+            }
+        }
+    };
+}
 
-        #[no_mangle]
-        #[link_section = ".entry"]
-        pub unsafe extern "C" fn initialize(addr: u32, clock: u32, op: u32) -> u32 {
-            if INIT_FLAG {
-                deinitialize();
-            }
-            INIT_FLAG = true;
-            let op = match op {
-                1 => $crate::Operation::Erase,
-                2 => $crate::Operation::Program,
-                3 => $crate::Operation::Verify,
-                _ => panic!("Invalid operation code.")
-            };
-            match <$algo as FlashOps>::create(addr, clock, op) {
-                Ok(instance) => {
-                    ALGO_INSTANCE.as_mut_ptr().write(instance);
-                    INIT_FLAG = true;
-                    0
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "program-pages")]
+macro_rules! program_pages {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn program_pages(addr: u32, size: u32, data: *const u8) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if size == 0 {
+                        return 0;
+                    }
+                    if TRANSFER_BUFFER && size > RAM_BUFFER_SIZE {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                    if !(data as usize).is_multiple_of(BUFFER_ALIGNMENT as usize) {
+                        return $crate::error_codes::MISALIGNED_BUFFER.get();
+                    }
                 }
-                Err(e) => e.get(),
+                $crate::busy_guard!();
+                $crate::bounds_check!($type, addr, size);
+                $crate::protected_check!($type, addr, size);
+                let instance = $crate::unlocked_instance!();
+                let data_slice: &[u8] = core::slice::from_raw_parts(data, size as usize);
+                match <$type as FlashOps>::program_pages(instance, addr, data_slice) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+        $crate::host! {
+            /// Safe host-side form of `program_pages`, taking the pages as a slice.
+            fn __program_pages(addr: u32, data: &[u8]) -> u32 {
+                program_pages(addr, data.len() as u32, data.as_ptr())
             }
         }
+    };
+}
 
-        #[no_mangle]
-        #[link_section = ".entry"]
-        pub unsafe extern "C" fn deinitialize() -> u32 {
-            if !INIT_FLAG {
-                return 1;
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "program-verify", feature = "full-symbol-table")))]
+macro_rules! program_and_verify {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "program-verify"), feature = "full-symbol-table"))]
+macro_rules! program_and_verify {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn program_and_verify(
+                _addr: u32,
+                _size: u32,
+                _data: *const u8,
+            ) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
             }
-            ALGO_INSTANCE.as_mut_ptr().drop_in_place();
-            INIT_FLAG = false;
-            0
         }
+    };
+}
 
-        #[no_mangle]
-        #[link_section = ".entry"]
-        pub unsafe extern "C" fn erase_sector(addr: u32) -> u32 {
-            if !INIT_FLAG {
-                return 1;
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "program-verify")]
+macro_rules! program_and_verify {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn program_and_verify(addr: u32, size: u32, data: *const u8) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if size == 0 {
+                        return 0;
+                    }
+                    if !size.is_multiple_of(PROGRAM_GRANULARITY) {
+                        return $crate::error_codes::UNALIGNED_PROGRAM.get();
+                    }
+                    if TRANSFER_BUFFER && size > RAM_BUFFER_SIZE / PAGE_BUFFERS {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                    if !(data as usize).is_multiple_of(BUFFER_ALIGNMENT as usize) {
+                        return $crate::error_codes::MISALIGNED_BUFFER.get();
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::bounds_check!($type, addr, size);
+                $crate::program_alignment_check!($type, addr);
+                $crate::protected_check!($type, addr, size);
+                let instance = $crate::unlocked_instance!();
+                let data_slice: &[u8] = core::slice::from_raw_parts(data, size as usize);
+                match <$type as FlashOps>::program_and_verify(instance, addr, data_slice) {
+                    Ok(()) => 0,
+                    Err(e) => e.get(),
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "estimate", feature = "full-symbol-table")))]
+macro_rules! estimated_duration {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "estimate"), feature = "full-symbol-table"))]
+macro_rules! estimated_duration {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn estimated_duration(
+                _op: u32,
+                _size: u32,
+                _out: *mut u32,
+            ) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
             }
-            let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
-            match <$algo as FlashOps>::erase_sector(instance, addr) {
-                Ok(()) => 0,
-                Err(e) => e.get(),
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "estimate")]
+macro_rules! estimated_duration {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn estimated_duration(op: u32, size: u32, out: *mut u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if out.is_null() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                }
+                $crate::busy_guard!();
+                let op = match <$crate::Operation as core::convert::TryFrom<u32>>::try_from(op) {
+                    Ok(op) => op,
+                    Err(e) => return e.get(),
+                };
+                let instance = &*ALGO_INSTANCE.as_mut_ptr();
+                out.write(<$type as FlashOps>::estimated_duration(instance, op, size));
+                0
             }
         }
+    };
+}
 
-        #[no_mangle]
-        #[link_section = ".entry"]
-        pub unsafe extern "C" fn program_page(addr: u32, size: u32, data: *const u8) -> u32 {
-            if !INIT_FLAG {
-                return 1;
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "erase-if-needed", feature = "full-symbol-table")))]
+macro_rules! erase_if_needed {
+    ($type:ty, [$($section:literal)?]) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "erase-if-needed"), feature = "full-symbol-table"))]
+macro_rules! erase_if_needed {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn erase_if_needed(_addr: u32, _erased: *mut u32) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
             }
-            let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
-            let data_slice: &[u8] = core::slice::from_raw_parts(data, size as usize);
-            match <$algo as FlashOps>::program_page(instance, addr, data_slice) {
-                Ok(()) => 0,
-                Err(e) => e.get(),
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "erase-if-needed")]
+macro_rules! erase_if_needed {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn erase_if_needed(addr: u32, erased: *mut u32) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if erased.is_null() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                }
+                $crate::busy_guard!();
+                $crate::bounds_check!($type, addr, 1);
+                $crate::erase_alignment_check!($type, addr);
+                $crate::protected_check!($type, addr, 1);
+                let instance = $crate::unlocked_instance!();
+                match <$type as FlashOps>::erase_if_needed(instance, addr) {
+                    Ok(did_erase) => {
+                        erased.write(did_erase as u32);
+                        0
+                    }
+                    Err(e) => e.get(),
+                }
             }
         }
+    };
+}
 
-        $crate::erase_chip!($algo);
-        $crate::verify!($algo);
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(feature = "program-counted", feature = "full-symbol-table")))]
+macro_rules! program_page_counted {
+    ($type:ty, [$($section:literal)?]) => {};
+}
 
-        #[allow(non_upper_case_globals)]
-        #[no_mangle]
-        #[used]
-        #[link_section = "DeviceData"]
-        pub static FlashDeviceInfo: FlashDevice = FlashDevice {
-            vers: 0x0,
-            dev_name: [0u8; 128],
-            dev_type: 5,
-            dev_addr: $addr,
-            device_size: $size,
-            page_size: $page_size,
-            _reserved: 0,
-            empty: $empty,
-            program_time_out: 1000,
-            erase_time_out: 2000,
-            flash_sectors: [
-                $(
-                    Sector { size: $sector_size, address: $sector_addr }
-                ),+,
-                Sector {
-                    size: 0xffff_ffff,
-                    address: 0xffff_ffff,
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "program-counted"), feature = "full-symbol-table"))]
+macro_rules! program_page_counted {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn program_page_counted(
+                _addr: u32,
+                _size: u32,
+                _data: *const u8,
+                _out: *mut u32,
+            ) -> u32 {
+                $crate::error_codes::UNSUPPORTED_OPERATION.get()
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "program-counted")]
+macro_rules! program_page_counted {
+    ($type:ty, [$($section:literal)?]) => {
+        $crate::entry! {
+            [$($section)?]
+            pub unsafe extern "C" fn program_page_counted(
+                addr: u32,
+                size: u32,
+                data: *const u8,
+                out: *mut u32,
+            ) -> u32 {
+                $crate::checks! {
+                    if !INIT_FLAG.load(core::sync::atomic::Ordering::Relaxed) {
+                        return $crate::error_codes::NOT_INITIALIZED.get();
+                    }
+                }
+                $crate::checks! {
+                    if out.is_null() {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                    if size == 0 {
+                        out.write(0);
+                        return 0;
+                    }
+                    if !size.is_multiple_of(PROGRAM_GRANULARITY) {
+                        return $crate::error_codes::UNALIGNED_PROGRAM.get();
+                    }
+                    if TRANSFER_BUFFER && size > RAM_BUFFER_SIZE / PAGE_BUFFERS {
+                        return $crate::error_codes::INVALID_ARGUMENT.get();
+                    }
+                    if !(data as usize).is_multiple_of(BUFFER_ALIGNMENT as usize) {
+                        return $crate::error_codes::MISALIGNED_BUFFER.get();
+                    }
                 }
-            ],
+                $crate::busy_guard!();
+                $crate::bounds_check!($type, addr, size);
+                $crate::program_alignment_check!($type, addr);
+                $crate::protected_check!($type, addr, size);
+                let instance = $crate::unlocked_instance!();
+                let data_slice: &[u8] = core::slice::from_raw_parts(data, size as usize);
+                match <$type as FlashOps>::program_page_counted(instance, addr, data_slice) {
+                    Ok(count) => {
+                        out.write(count);
+                        0
+                    }
+                    Err(e) => e.get(),
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "reentrancy-guard"))]
+macro_rules! busy_guard {
+    () => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "reentrancy-guard")]
+macro_rules! busy_guard {
+    () => {
+        let _guard = match ALGO_INSTANCE.enter() {
+            Some(guard) => guard,
+            None => return $crate::error_codes::BUSY.get(),
         };
+    };
+}
 
-        #[repr(C)]
-        pub struct FlashDevice {
-            vers: u16,
-            dev_name: [u8; 128],
-            dev_type: u16,
-            dev_addr: u32,
-            device_size: u32,
-            page_size: u32,
-            _reserved: u32,
-            empty: u8,
-            program_time_out: u32,
-            erase_time_out: u32,
-            flash_sectors: [Sector; $crate::count!($($sector_size)*) + 1],
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "bounds-check"))]
+macro_rules! bounds_check {
+    ($type:ty, $addr:expr, $size:expr) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "bounds-check")]
+macro_rules! bounds_check {
+    ($type:ty, $addr:expr, $size:expr) => {
+        $crate::checks! {
+            if !$crate::in_bounds::<$type>($addr, $size) {
+                return $crate::error_codes::ADDRESS_OUT_OF_RANGE.get();
+            }
         }
+    };
+}
 
-        #[repr(C)]
-        #[derive(Copy, Clone)]
-        pub struct Sector {
-            size: u32,
-            address: u32,
+/// Evaluates to the algorithm instance after unlocking it, or returns the error of
+/// [`FlashOps::unlock`] from the calling entry point.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! unlocked_instance {
+    () => {
+        match ALGO_INSTANCE.unlocked() {
+            Ok(instance) => &mut *instance,
+            Err(e) => return e.get(),
         }
     };
 }
 
 #[doc(hidden)]
 #[macro_export]
-#[cfg(not(feature = "erase-chip"))]
-macro_rules! erase_chip {
-    ($type:ty) => {};
+#[cfg(not(feature = "descriptor-v2"))]
+macro_rules! descriptor_v2 {
+    ($($item:item)*) => {};
 }
 
 #[doc(hidden)]
 #[macro_export]
-#[cfg(feature = "erase-chip")]
-macro_rules! erase_chip {
-    ($type:ty) => {
-        #[no_mangle]
-        #[link_section = ".entry"]
-        pub unsafe extern "C" fn erase_chip() -> u32 {
-            if !INIT_FLAG {
-                return 1;
-            }
-            let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
-            match <$type as FlashOps>::erase_chip(instance) {
-                Ok(()) => 0,
-                Err(e) => e.get(),
+#[cfg(feature = "descriptor-v2")]
+macro_rules! descriptor_v2 {
+    ($($item:item)*) => {
+        $($item)*
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! sector_sentinel {
+    (@count none) => {
+        0
+    };
+    (@count {size: $size:expr, address: $address:expr $(,)?}) => {
+        1
+    };
+    (@entries none) => {
+        []
+    };
+    (@entries {size: $size:expr, address: $address:expr $(,)?}) => {
+        [Sector {
+            size: $size,
+            address: $address,
+        }]
+    };
+    (@$mode:ident $other:tt) => {
+        compile_error!("sector_sentinel must be `none` or `{size: ..., address: ...}`")
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "erase-progress"))]
+macro_rules! erase_progress {
+    () => {};
+    (@geometry) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "erase-progress")]
+macro_rules! erase_progress {
+    () => {
+        $crate::state! {
+            export
+            #[allow(non_upper_case_globals)]
+            #[used]
+            pub static FlashEraseProgress: core::sync::atomic::AtomicU32 =
+                core::sync::atomic::AtomicU32::new(0);
+        }
+    };
+    (@geometry) => {
+        fn erase_progress() -> Option<&'static core::sync::atomic::AtomicU32> {
+            Some(&FlashEraseProgress)
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "ecc"))]
+macro_rules! ecc_size {
+    () => {};
+    (@check $size:expr) => {};
+    ($size:expr) => {
+        core::compile_error!("`ecc_size` requires the `ecc` feature");
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "ecc")]
+macro_rules! ecc_size {
+    () => {};
+    (@check $size:expr) => {
+        const _: () = assert!(
+            $size as usize <= $crate::MAX_ECC_SIZE,
+            "ecc_size must be at most MAX_ECC_SIZE"
+        );
+    };
+    ($size:expr) => {
+        const ECC_SIZE: u32 = $size;
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "device-id"))]
+macro_rules! device_id_check {
+    ($type:ty, $instance:ident, $op:ident, []) => {};
+    ($type:ty, $instance:ident, $op:ident, [$($id:expr),+]) => {
+        core::compile_error!("`allowed_ids` requires the `device-id` feature");
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "device-id")]
+macro_rules! device_id_check {
+    ($type:ty, $instance:ident, $op:ident, []) => {};
+    ($type:ty, $instance:ident, $op:ident, [$($id:expr),+]) => {
+        let mut $instance = $instance;
+        if $op != $crate::Operation::Verify {
+            let result = match <$type as FlashOps>::read_device_id(&mut $instance) {
+                Ok(id) if [$($id),+].contains(&id) => Ok(()),
+                Ok(_) => Err($crate::error_codes::WRONG_DEVICE),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                let _ = <$type as FlashOps>::deinit(&mut $instance);
+                return e.get();
             }
         }
     };
@@ -188,39 +4194,98 @@ macro_rules! erase_chip {
 
 #[doc(hidden)]
 #[macro_export]
-#[cfg(not(feature = "verify"))]
-macro_rules! verify {
-    ($type:ty) => {};
+#[cfg(not(feature = "strict-program"))]
+macro_rules! program_alignment_check {
+    ($type:ty, $addr:expr) => {};
 }
 
 #[doc(hidden)]
 #[macro_export]
-#[cfg(feature = "verify")]
-macro_rules! verify {
-    ($type:ty) => {
-        #[no_mangle]
-        #[link_section = ".entry"]
-        pub unsafe extern "C" fn verify(addr: u32, size: u32, data: *const u8) -> u32 {
-            if !INIT_FLAG {
-                return 1;
+#[cfg(feature = "strict-program")]
+macro_rules! program_alignment_check {
+    ($type:ty, $addr:expr) => {
+        $crate::checks! {
+            let aligned = match <$type as $crate::FlashGeometry>::DATA_REGION {
+                Some(region) if region.contains($addr, 1) => {
+                    ($addr - region.address) % region.page_size == 0
+                }
+                _ => <$type as $crate::FlashGeometry>::is_page_aligned($addr),
+            };
+            if !aligned {
+                return $crate::error_codes::UNALIGNED_PROGRAM.get();
             }
-            let instance = &mut *ALGO_INSTANCE.as_mut_ptr();
-            let data_slice = if data.is_null() {
-                None
-            } else {
-                Some(unsafe { core::slice::from_raw_parts(data, size as usize) })
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "strict-erase"))]
+macro_rules! erase_alignment_check {
+    ($type:ty, $addr:expr) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "strict-erase")]
+macro_rules! erase_alignment_check {
+    ($type:ty, $addr:expr) => {
+        $crate::checks! {
+            let aligned = match <$type as $crate::FlashGeometry>::DATA_REGION {
+                Some(region) if region.contains($addr, 1) => {
+                    ($addr - region.address) % region.page_size == 0
+                }
+                _ => $crate::sector_aligned_base::<$type>($addr) == Some($addr),
             };
-            match <$type as FlashOps>::verify(instance, addr, size, data_slice) {
-                Ok(()) => 0,
-                Err(e) => e.get(),
+            if !aligned {
+                return $crate::error_codes::UNALIGNED_ERASE.get();
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "no-checks"))]
+macro_rules! checks {
+    ($($check:tt)*) => {
+        $($check)*
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "no-checks")]
+macro_rules! checks {
+    ($($check:tt)*) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! protected_check {
+    ($type:ty, $addr:expr, $size:expr) => {
+        $crate::checks! {
+            if $crate::overlaps_protected::<$type>(PROTECTED_SECTORS, $addr, $size) {
+                return $crate::error_codes::SECTOR_PROTECTED.get();
             }
         }
     };
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! or_default {
+    (, $default:expr) => {
+        $default
+    };
+    ($value:expr, $default:expr) => {
+        $value
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! count {
-    () => (0usize);
-    ( $x:tt $($xs:tt)* ) => (1usize + count!($($xs)*));
+    (@unit $x:tt) => (());
+    ( $($x:tt)* ) => (<[()]>::len(&[$($crate::count!(@unit $x)),*]));
 }
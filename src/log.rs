@@ -0,0 +1,62 @@
+use core::cell::UnsafeCell;
+
+/// Sink for short progress messages. The default discards them.
+///
+/// `write` takes `&self` rather than `&mut self` because the sink the host reads is normally
+/// the `FlashAlgorithmLog` static emitted by the `log_buffer` key, which an algorithm can
+/// only reach through a shared reference short of a `static mut`. [`LogBuffer`] therefore
+/// writes through interior mutability, relying on entry points running one at a time.
+pub trait FlashLog {
+    fn write(&self, _message: &str) {}
+}
+
+/// Ring buffer of log messages, each terminated by `\n`, that the host reads out of target
+/// RAM.
+///
+/// The `#[repr(C)]` layout is a `u32` count of bytes written so far, a `u32` capacity `N`, then
+/// `N` bytes of data. The newest byte sits at index `(written - 1) % N` and the buffer holds the
+/// last `min(written, N)` bytes.
+#[repr(C)]
+pub struct LogBuffer<const N: usize> {
+    written: UnsafeCell<u32>,
+    capacity: u32,
+    data: UnsafeCell<[u8; N]>,
+}
+
+unsafe impl<const N: usize> Sync for LogBuffer<N> {}
+
+impl<const N: usize> LogBuffer<N> {
+    pub const fn new() -> Self {
+        const { assert!(N > 0, "a LogBuffer needs room for at least one byte") };
+        Self {
+            written: UnsafeCell::new(0),
+            capacity: N as u32,
+            data: UnsafeCell::new([0; N]),
+        }
+    }
+
+    fn push(&self, byte: u8) {
+        // Entry points run one at a time on a single core, so nothing else touches the buffer.
+        unsafe {
+            let written = self.written.get().read_volatile();
+            let index = written as usize % N;
+            (self.data.get() as *mut u8).add(index).write_volatile(byte);
+            self.written.get().write_volatile(written.wrapping_add(1));
+        }
+    }
+}
+
+impl<const N: usize> Default for LogBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FlashLog for LogBuffer<N> {
+    fn write(&self, message: &str) {
+        for &byte in message.as_bytes() {
+            self.push(byte);
+        }
+        self.push(b'\n');
+    }
+}
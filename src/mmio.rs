@@ -0,0 +1,273 @@
+use crate::{Error, ErrorCode, WordOrder};
+
+/// Access width of a flash program operation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Word {
+    U8 = 1,
+    U16 = 2,
+    U32 = 4,
+}
+
+impl Word {
+    pub const fn bytes(self) -> usize {
+        self as usize
+    }
+}
+
+/// Register and flash accesses made by [`MmioController`], so the same sequences can run
+/// against a model on the host.
+pub trait Bus {
+    /// # Safety
+    ///
+    /// `address` must be a readable 32-bit register.
+    unsafe fn read32(&mut self, address: u32) -> u32;
+
+    /// Writes the low `width` bytes of `value`.
+    ///
+    /// # Safety
+    ///
+    /// `address` must be a register or flash location writable with `width`.
+    unsafe fn write(&mut self, address: u32, value: u32, width: Word);
+}
+
+/// [`Bus`] performing volatile accesses to the physical address space.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Mmio;
+
+impl Bus for Mmio {
+    unsafe fn read32(&mut self, address: u32) -> u32 {
+        core::ptr::read_volatile(address as usize as *const u32)
+    }
+
+    unsafe fn write(&mut self, address: u32, value: u32, width: Word) {
+        let pointer = address as usize;
+        match width {
+            Word::U8 => core::ptr::write_volatile(pointer as *mut u8, value as u8),
+            Word::U16 => core::ptr::write_volatile(pointer as *mut u16, value as u16),
+            Word::U32 => core::ptr::write_volatile(pointer as *mut u32, value),
+        }
+    }
+}
+
+/// Register map of a simple memory-mapped flash controller: unlocked by writing keys, programmed
+/// by writing words to the mapped flash with a program bit set, and erased by writing the sector
+/// address to a register and setting a start bit. [`mmio_flash!`](crate::mmio_flash) turns one
+/// into a [`FlashOps`](crate::FlashOps) implementation.
+#[derive(Debug, Copy, Clone)]
+pub struct MmioController {
+    /// `(register, key)` writes performed, in order, to unlock the controller.
+    pub unlock: &'static [(u32, u32)],
+    /// Address of the control register.
+    pub control: u32,
+    /// Control bits enabling programming.
+    pub program: u32,
+    /// Control bits selecting a sector erase.
+    pub erase: u32,
+    /// Control bits starting the selected erase, written together with `erase`.
+    pub start: u32,
+    /// Control bits locking the controller again.
+    pub lock: u32,
+    /// Address of the register taking the address of the sector to erase.
+    pub erase_address: u32,
+    /// Address of the status register.
+    pub status: u32,
+    /// Status bits set while an operation is in progress.
+    pub busy: u32,
+    /// Status bits reporting a failed operation.
+    pub errors: u32,
+    /// Width of each program access.
+    pub word: Word,
+    /// Value used to pad a final partial word.
+    pub empty: u8,
+    /// Status reads after which an operation still `busy` fails with
+    /// [`ErrorCode::Timeout`].
+    pub max_polls: u32,
+}
+
+impl MmioController {
+    /// # Safety
+    ///
+    /// The controller must describe the registers reachable through `bus`.
+    pub unsafe fn unlock<B: Bus>(&self, bus: &mut B) {
+        for &(register, key) in self.unlock {
+            bus.write(register, key, Word::U32);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The controller must describe the registers reachable through `bus`.
+    pub unsafe fn lock<B: Bus>(&self, bus: &mut B) {
+        bus.write(self.control, self.lock, Word::U32);
+    }
+
+    /// # Safety
+    ///
+    /// The controller must describe the registers reachable through `bus`, and `address` must
+    /// lie in the flash it controls.
+    pub unsafe fn erase_sector<B: Bus>(&self, bus: &mut B, address: u32) -> Result<(), Error> {
+        self.wait(bus)?;
+        bus.write(self.control, self.erase, Word::U32);
+        bus.write(self.erase_address, address, Word::U32);
+        bus.write(self.control, self.erase | self.start, Word::U32);
+        let result = self.finish(bus, ErrorCode::EraseFailed);
+        bus.write(self.control, 0, Word::U32);
+        result
+    }
+
+    /// Programs `data` one `word` at a time, padding a final partial word with `empty`.
+    /// Words are assembled little-endian; see [`MmioController::program_page_ordered`].
+    ///
+    /// # Safety
+    ///
+    /// The controller must describe the registers reachable through `bus`, and the written
+    /// range must lie in the flash it controls.
+    pub unsafe fn program_page<B: Bus>(
+        &self,
+        bus: &mut B,
+        address: u32,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.program_page_ordered(bus, address, data, WordOrder::LittleEndian)
+    }
+
+    /// Like [`MmioController::program_page`], assembling each word from `data` in `order`.
+    ///
+    /// # Safety
+    ///
+    /// The controller must describe the registers reachable through `bus`, and the written
+    /// range must lie in the flash it controls.
+    pub unsafe fn program_page_ordered<B: Bus>(
+        &self,
+        bus: &mut B,
+        address: u32,
+        data: &[u8],
+        order: WordOrder,
+    ) -> Result<(), Error> {
+        self.wait(bus)?;
+        bus.write(self.control, self.program, Word::U32);
+        let mut result = Ok(());
+        let mut offset = address;
+        for chunk in data.chunks(self.word.bytes()) {
+            let mut bytes = [self.empty; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            let value = match self.word {
+                Word::U8 => bytes[0] as u32,
+                Word::U16 => order.read_u16(&bytes, 0).unwrap_or_default() as u32,
+                Word::U32 => order.read_u32(&bytes, 0).unwrap_or_default(),
+            };
+            bus.write(offset, value, self.word);
+            result = self.finish(bus, ErrorCode::ProgramFailed);
+            if result.is_err() {
+                break;
+            }
+            offset += self.word.bytes() as u32;
+        }
+        bus.write(self.control, 0, Word::U32);
+        result
+    }
+
+    unsafe fn wait<B: Bus>(&self, bus: &mut B) -> Result<u32, Error> {
+        for _ in 0..self.max_polls {
+            let status = bus.read32(self.status);
+            if status & self.busy == 0 {
+                return Ok(status);
+            }
+        }
+        Err(ErrorCode::Timeout.into())
+    }
+
+    unsafe fn finish<B: Bus>(&self, bus: &mut B, error: ErrorCode) -> Result<(), Error> {
+        if self.wait(bus)? & self.errors != 0 {
+            Err(error.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Defines `$name<B = Mmio>` and implements [`FlashOps`](crate::FlashOps) for it from an
+/// [`MmioController`]: [`unlock`](crate::FlashOps::unlock) and [`lock`](crate::FlashOps::lock)
+/// write the controller's key and lock sequences, and `erase_sector` and `program_page` run
+/// its erase and program sequences on the bus `B`, assembling words in the `word_order`
+/// declared to [`flash_algorithm!`](crate::flash_algorithm).
+///
+/// Methods required by enabled features, such as `verify`, go in an optional trailing block
+/// that is pasted into the impl. The leading `unsafe` is the caller's assertion that the
+/// controller describes the target's registers, since the generated methods access them
+/// without further checks. Pass the type to [`flash_algorithm!`](crate::flash_algorithm) as
+/// usual, which the generated `program_page` relies on; host tests can pass it with a
+/// modelled [`Bus`], as `$name<ModelBus>`, instead of [`Mmio`].
+///
+/// ```ignore
+/// const CONTROLLER: MmioController = MmioController {
+///     unlock: &[(0x4002_2004, 0x4567_0123), (0x4002_2004, 0xCDEF_89AB)],
+///     control: 0x4002_2010,
+///     program: 1 << 0,
+///     erase: 1 << 1,
+///     start: 1 << 6,
+///     lock: 1 << 7,
+///     erase_address: 0x4002_2014,
+///     status: 0x4002_200C,
+///     busy: 1 << 0,
+///     errors: (1 << 2) | (1 << 4),
+///     word: Word::U16,
+///     empty: 0xFF,
+///     max_polls: 1_000_000,
+/// };
+///
+/// mmio_flash!(unsafe pub struct Stm32f1, CONTROLLER);
+///
+/// flash_algorithm!(Stm32f1, {
+///     flash_address: 0x0800_0000,
+///     flash_size: 0x10000,
+///     page_size: 0x400,
+///     empty_value: 0xFF,
+///     sectors: [{size: 0x400, address: 0x0}]
+/// });
+/// ```
+#[macro_export]
+macro_rules! mmio_flash {
+    (unsafe $vis:vis struct $name:ident, $controller:expr $(, { $($items:tt)* })?) => {
+        $vis struct $name<B = $crate::Mmio>(pub B);
+
+        impl<B: $crate::Bus + Default> $crate::FlashOps for $name<B>
+        where
+            Self: $crate::FlashGeometry,
+        {
+            fn create(
+                _address: u32,
+                _clock: u32,
+                _operation: $crate::Operation,
+            ) -> Result<Self, $crate::Error> {
+                Ok(Self(B::default()))
+            }
+
+            fn unlock(&mut self) -> Result<(), $crate::Error> {
+                unsafe { $controller.unlock(&mut self.0) };
+                Ok(())
+            }
+
+            fn erase_sector(&mut self, address: u32) -> Result<(), $crate::Error> {
+                unsafe { $controller.erase_sector(&mut self.0, address) }
+            }
+
+            fn program_page(&mut self, address: u32, data: &[u8]) -> Result<(), $crate::Error> {
+                let order = <Self as $crate::FlashGeometry>::WORD_ORDER;
+                unsafe { $controller.program_page_ordered(&mut self.0, address, data, order) }
+            }
+
+            fn lock(&mut self) -> Result<(), $crate::Error> {
+                unsafe { $controller.lock(&mut self.0) };
+                Ok(())
+            }
+
+            $($($items)*)?
+        }
+    };
+    ($vis:vis struct $($rest:tt)*) => {
+        core::compile_error!(
+            "mmio_flash! needs `unsafe` before the struct, as the assertion that the controller describes the target's registers"
+        );
+    };
+}
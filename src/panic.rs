@@ -0,0 +1,25 @@
+#[cfg(all(feature = "panic-udf", feature = "panic-bkpt"))]
+compile_error!("features `panic-udf` and `panic-bkpt` are mutually exclusive");
+
+// `std` brings its own panic handler, which would collide with this one.
+#[cfg(feature = "std")]
+compile_error!(
+    "feature `panic-handler` cannot be combined with `std`; \
+     disable `panic-handler`, `panic-udf` and `panic-bkpt` to use another handler"
+);
+
+#[cfg(not(any(test, feature = "std")))]
+#[panic_handler]
+fn handle_panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe {
+        #[cfg(all(not(target_arch = "riscv32"), not(feature = "panic-bkpt")))]
+        core::arch::asm!("udf #0");
+        #[cfg(all(not(target_arch = "riscv32"), feature = "panic-bkpt"))]
+        core::arch::asm!("bkpt #0");
+        #[cfg(all(target_arch = "riscv32", not(feature = "panic-bkpt")))]
+        core::arch::asm!("unimp");
+        #[cfg(all(target_arch = "riscv32", feature = "panic-bkpt"))]
+        core::arch::asm!("ebreak");
+        core::hint::unreachable_unchecked();
+    }
+}
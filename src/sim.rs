@@ -0,0 +1,152 @@
+use std::vec;
+use std::vec::Vec;
+
+use crate::{crc32, sector_containing, Error, ErrorCode};
+
+/// In-memory flash model for exercising algorithm logic on the host.
+///
+/// Sectors are described the same way as in `flash_algorithm!`: `(size, address)` pairs with
+/// addresses relative to the start of the flash, each size applying up to the next entry.
+/// Erasing fills a sector with the empty value, or with the pattern set by
+/// [`FlashSim::with_empty_pattern`], and programming only succeeds on bytes that are still
+/// erased.
+pub struct FlashSim {
+    address: u32,
+    page_size: u32,
+    empty: u8,
+    pattern: Vec<u8>,
+    sectors: Vec<(u32, u32)>,
+    memory: Vec<u8>,
+    device_id: u32,
+}
+
+impl FlashSim {
+    pub fn new(address: u32, size: u32, page_size: u32, empty: u8, sectors: &[(u32, u32)]) -> Self {
+        Self {
+            address,
+            page_size,
+            empty,
+            pattern: vec![empty],
+            sectors: sectors.to_vec(),
+            memory: vec![empty; size as usize],
+            device_id: 0,
+        }
+    }
+
+    /// Sets the value returned by [`FlashSim::read_device_id`], `0` by default.
+    pub fn with_device_id(mut self, device_id: u32) -> Self {
+        self.device_id = device_id;
+        self
+    }
+
+    /// Makes erased flash hold `pattern` repeated from the start of the flash, like the
+    /// `empty_pattern` key of `flash_algorithm!`, and erases the whole memory to it.
+    pub fn with_empty_pattern(mut self, pattern: &[u8]) -> Self {
+        assert!(
+            !pattern.is_empty(),
+            "the empty pattern needs at least one byte"
+        );
+        self.pattern = pattern.to_vec();
+        let all = 0..self.memory.len();
+        self.fill_erased(all);
+        self
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn empty_value(&self) -> u8 {
+        self.empty
+    }
+
+    /// Returns the `(base, size)` of the sector containing `address`.
+    pub fn sector(&self, address: u32) -> Option<(u32, u32)> {
+        sector_containing(
+            &self.sectors,
+            self.address,
+            self.memory.len() as u32,
+            address,
+        )
+    }
+
+    pub fn erase_sector(&mut self, address: u32) -> Result<(), Error> {
+        let (base, size) = self.sector(address).ok_or(ErrorCode::AddressOutOfRange)?;
+        let range = self.range(base, size)?;
+        self.fill_erased(range);
+        Ok(())
+    }
+
+    pub fn program_page(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        let range = self.range(address, data.len() as u32)?;
+        if range.start % self.page_size as usize + data.len() > self.page_size as usize {
+            return Err(ErrorCode::ProgramFailed.into());
+        }
+        if !self.is_erased(range.clone()) {
+            return Err(ErrorCode::ProgramFailed.into());
+        }
+        self.memory[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    pub fn read(&mut self, address: u32, data: &mut [u8]) -> Result<(), Error> {
+        let range = self.range(address, data.len() as u32)?;
+        data.copy_from_slice(&self.memory[range]);
+        Ok(())
+    }
+
+    /// Compares the region against `data`, or against its erased contents when `data` is
+    /// `None`.
+    pub fn verify(&mut self, address: u32, size: u32, data: Option<&[u8]>) -> Result<(), Error> {
+        let range = self.range(address, size)?;
+        let matches = match data {
+            Some(data) => data.len() == range.len() && self.memory[range] == *data,
+            None => self.is_erased(range),
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(ErrorCode::VerifyMismatch.into())
+        }
+    }
+
+    pub fn is_sector_erased(&mut self, address: u32) -> Result<bool, Error> {
+        let (base, size) = self.sector(address).ok_or(ErrorCode::AddressOutOfRange)?;
+        let range = self.range(base, size)?;
+        Ok(self.is_erased(range))
+    }
+
+    pub fn read_device_id(&mut self) -> Result<u32, Error> {
+        Ok(self.device_id)
+    }
+
+    pub fn checksum(&mut self, address: u32, size: u32) -> Result<u32, Error> {
+        let range = self.range(address, size)?;
+        Ok(crc32(0, &self.memory[range]))
+    }
+
+    fn erased_byte(&self, offset: usize) -> u8 {
+        self.pattern[offset % self.pattern.len()]
+    }
+
+    fn is_erased(&self, mut range: core::ops::Range<usize>) -> bool {
+        range.all(|offset| self.memory[offset] == self.erased_byte(offset))
+    }
+
+    fn fill_erased(&mut self, range: core::ops::Range<usize>) {
+        for offset in range {
+            self.memory[offset] = self.erased_byte(offset);
+        }
+    }
+
+    fn range(&self, address: u32, size: u32) -> Result<core::ops::Range<usize>, Error> {
+        let start = address
+            .checked_sub(self.address)
+            .ok_or(ErrorCode::AddressOutOfRange)? as usize;
+        let end = start + size as usize;
+        if end > self.memory.len() {
+            return Err(ErrorCode::AddressOutOfRange.into());
+        }
+        Ok(start..end)
+    }
+}
@@ -0,0 +1,26 @@
+use std::fmt::Write;
+use std::string::String;
+
+use crate::FlashGeometry;
+
+/// Renders the `flash_properties` stanza of a probe-rs target description for `A`, ready to
+/// be pasted under the flash algorithm entry of a `target.yaml`.
+pub fn flash_properties_yaml<A: FlashGeometry>() -> String {
+    let mut yaml = String::new();
+    let end = A::FLASH_ADDRESS as u64 + A::FLASH_SIZE as u64;
+    // Writing to a `String` cannot fail.
+    let _ = writeln!(yaml, "flash_properties:");
+    let _ = writeln!(yaml, "  address_range:");
+    let _ = writeln!(yaml, "    start: {:#x}", A::FLASH_ADDRESS);
+    let _ = writeln!(yaml, "    end: {:#x}", end);
+    let _ = writeln!(yaml, "  page_size: {:#x}", A::PAGE_SIZE);
+    let _ = writeln!(yaml, "  erased_byte_value: {:#x}", A::EMPTY_VALUE);
+    let _ = writeln!(yaml, "  program_page_timeout: {}", A::PROGRAM_TIMEOUT);
+    let _ = writeln!(yaml, "  erase_sector_timeout: {}", A::ERASE_TIMEOUT);
+    let _ = writeln!(yaml, "  sectors:");
+    for &(size, address) in A::SECTORS {
+        let _ = writeln!(yaml, "  - size: {:#x}", size);
+        let _ = writeln!(yaml, "    address: {:#x}", address);
+    }
+    yaml
+}
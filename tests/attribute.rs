@@ -0,0 +1,215 @@
+//! Checks `flashops::attr::flash_algorithm` against the declarative `flash_algorithm!` it
+//! expands to.
+
+/// Defines `Algo` with the attribute applied to its `impl FlashOps` block.
+macro_rules! algorithm {
+    ($($args:tt)*) => {
+        pub struct Algo;
+
+        #[flashops::attr::flash_algorithm($($args)*)]
+        impl FlashOps for Algo {
+            fn create(_address: u32, _clock: u32, _operation: Operation) -> Result<Self, Error> {
+                Ok(Self)
+            }
+
+            fn erase_sector(&mut self, _address: u32) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn program_page(&mut self, _address: u32, _data: &[u8]) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn read(&mut self, _address: u32, _data: &mut [u8]) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn blank_check(&mut self, _address: u32, _size: u32) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn checksum(&mut self, _address: u32, _size: u32) -> Result<u32, Error> {
+                Ok(0)
+            }
+
+            fn read_device_id(&mut self) -> Result<u32, Error> {
+                Ok(0)
+            }
+
+            fn write_config(&mut self, _reg: u32, _value: u32) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Every key that the features of the test build accept, in the reverse of the order
+/// `flash_algorithm!` expects them.
+mod every_key {
+    use flashops::{error_codes, DataRegion, Error, FlashGeometry, FlashOps, Operation, WordOrder};
+
+    algorithm!(
+        buffer_alignment = 4,
+        requires_cache_disable = true,
+        word_order = WordOrder::BigEndian,
+        write_chunk = 0x40,
+        transfer_buffer = true,
+        sector_sentinel = none,
+        data_region = {address: 0x1FFF_0000, size: 0x400, page_size: 0x100},
+        empty_pattern = [0xFF, 0x00],
+        program_granularity = 4,
+        protected_sectors = [0x0800_0C00],
+        pad_pages = true,
+        device_section = "DevDscrAttr",
+        entry_section = ".text.attr",
+        init_retries = 3,
+        clock_range = {min: 1_000, max: 2_000},
+        device_address = 0x0800_0000,
+        version = 0x101,
+        page_buffers = 2,
+        ram_buffer_size = 0x400,
+        stack_size = 1024,
+        erase_timeout = 300,
+        program_timeout = 200,
+        device_type = 6,
+        device_name = "Attr",
+        sectors = [{size: 0x400, address: 0x0}],
+        empty_value = 0xFF,
+        page_size = 0x100,
+        flash_size = 0x1000,
+        flash_address = 0x0800_0000,
+    );
+
+    #[test]
+    fn every_key_reaches_the_expansion() {
+        assert_eq!(Algo::FLASH_ADDRESS, 0x0800_0000);
+        assert_eq!(Algo::FLASH_SIZE, 0x1000);
+        assert_eq!(Algo::PAGE_SIZE, 0x100);
+        assert_eq!(Algo::EMPTY_VALUE, 0xFF);
+        assert_eq!(
+            SECTORS,
+            [Sector {
+                size: 0x400,
+                address: 0x0
+            }]
+        );
+        assert_eq!(FlashDeviceInfo.dev_name[..5], *b"Attr\0");
+        assert_eq!(FlashDeviceInfo.dev_type, 6);
+        assert_eq!(FlashDeviceInfo.program_time_out, 200);
+        assert_eq!(FlashDeviceInfo.erase_time_out, 300);
+        assert_eq!(core::mem::size_of_val(&FlashAlgorithmStack), 1024);
+        assert_eq!(FlashAlgorithmInfo.ram_buffer_size, 0x400);
+        assert_eq!(FlashAlgorithmInfo.page_buffers, 2);
+        assert_eq!(FlashDeviceInfo.vers, 0x101);
+        assert_eq!(FlashDeviceInfo.dev_addr, 0x0800_0000);
+        assert_eq!(
+            unsafe { initialize(0x0800_0000, 0, 1) },
+            error_codes::CLOCK_OUT_OF_RANGE.get()
+        );
+        assert_eq!(PROTECTED_SECTORS, [0x0800_0C00]);
+        assert_eq!(FlashAlgorithmInfo.program_granularity, 4);
+        assert_eq!(Algo::EMPTY_PATTERN, [0xFF, 0x00]);
+        assert_eq!(
+            Algo::DATA_REGION,
+            Some(DataRegion {
+                address: 0x1FFF_0000,
+                size: 0x400,
+                page_size: 0x100,
+                program_granularity: 1,
+            })
+        );
+        assert!(SENTINEL.is_empty());
+        const { assert!(TRANSFER_BUFFER) };
+        assert_eq!(Algo::WRITE_CHUNK, 0x40);
+        assert_eq!(Algo::WORD_ORDER, WordOrder::BigEndian);
+        assert_eq!(ALGORITHM_FLAGS, flashops::ALGORITHM_FLAG_CACHE_DISABLE);
+        assert_eq!(FlashAlgorithmInfo.buffer_alignment, 4);
+    }
+}
+
+mod regions {
+    use flashops::{Error, FlashGeometry, FlashOps, Operation};
+
+    algorithm!(
+        empty_value = 0xFF,
+        page_size = 0x100,
+        regions = [
+            {address: 0x0800_0000, size: 0x1000, sectors: [{size: 0x400, address: 0x0}]},
+            {address: 0x0810_0000, size: 0x800, sectors: [{size: 0x800, address: 0x0}]}
+        ],
+    );
+
+    #[test]
+    fn regions_reach_the_expansion() {
+        assert_eq!(Algo::REGIONS, [(0x0800_0000, 0x1000), (0x0810_0000, 0x800)]);
+    }
+}
+
+/// A crate that renames the `flashops` dependency points the attribute at it with `crate`.
+mod renamed {
+    use flashops::{Error, FlashOps, Operation};
+
+    mod shim {
+        macro_rules! flash_algorithm {
+            ($algo:ty, { $($keys:tt)* }) => {
+                pub const KEYS: &str = stringify!($($keys)*);
+            };
+        }
+        pub(crate) use flash_algorithm;
+    }
+
+    algorithm!(
+        crate = self::shim,
+        flash_address = 0x0800_0000,
+        flash_size = 0x1000,
+        page_size = 0x100,
+        empty_value = 0xFF,
+        sectors = [{size: 0x400, address: 0x0}],
+    );
+
+    #[test]
+    fn crate_replaces_the_flashops_path() {
+        assert!(KEYS.starts_with("flash_address : 0x0800_0000"), "{KEYS}");
+        assert!(Algo::create(0x0800_0000, 0, Operation::Erase).is_ok());
+    }
+}
+
+/// `KEYS` must list the keys of the main `flash_algorithm!` arm in the same order, with
+/// `regions` taking the place the regions arm gives it, or sorting arguments into it would
+/// produce invocations the declarative macro rejects.
+#[test]
+fn keys_follow_the_declarative_macro() {
+    let source = include_str!("../src/lib.rs");
+    let arm = source
+        .split("macro_rules! flash_algorithm {")
+        .nth(1)
+        .and_then(|rest| rest.split("}) => {").next())
+        .unwrap();
+    let mut lines = arm.lines().filter(|line| !line.trim().is_empty());
+    let first = lines.next().unwrap();
+    let fixed = &first[first.find('{').unwrap() + 1..first.find("sectors:").unwrap()];
+    let mut expected: Vec<&str> = fixed
+        .split(", ")
+        .filter_map(|key| key.split_once(':').map(|(key, _)| key))
+        .collect();
+    expected.insert(2, "regions");
+    expected.push("sectors");
+    // Optional keys sit one level into the arm; deeper lines are the fields of a key.
+    expected.extend(lines.filter_map(|line| {
+        let key = line.strip_prefix("        $(, ")?.split_once(':')?.0;
+        (!key.starts_with('@')).then_some(key)
+    }));
+
+    let macros = include_str!("../macros/src/lib.rs");
+    let list = macros
+        .split("const KEYS: &[&str] = &[")
+        .nth(1)
+        .and_then(|rest| rest.split("];").next())
+        .unwrap();
+    let keys: Vec<&str> = list
+        .split(',')
+        .map(|key| key.trim().trim_matches('"'))
+        .filter(|key| !key.is_empty())
+        .collect();
+    assert_eq!(keys, expected);
+}
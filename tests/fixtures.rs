@@ -0,0 +1,239 @@
+//! Builds the algorithm crate in `tests/fixtures/algo` the way a downstream user would, to
+//! check what this crate's own test build cannot see: warnings in the `no_std` expansion and
+//! the code generated for other targets.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Builds the fixture under `-D warnings` for `target`, or the host when it is `None`, with
+/// the given `flashops` features, and returns the emitted assembly. A feature written as
+/// `flashops-fixture/<name>` selects one of the fixture's own features instead. Returns `None` without
+/// building when the target's standard library is not installed.
+fn build(name: &str, target: Option<&str>, features: &[&str]) -> Option<String> {
+    if let Some(target) = target {
+        if !installed(target) {
+            eprintln!("skipping {name}: the {target} target is not installed");
+            return None;
+        }
+    }
+    let output = cargo(name, target, features)
+        .output()
+        .expect("failed to run cargo");
+    assert!(
+        output.status.success(),
+        "fixture build failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let target_dir = target_dir(name);
+    let deps = match target {
+        Some(target) => target_dir.join(target).join("release/deps"),
+        None => target_dir.join("release/deps"),
+    };
+    Some(fs::read_to_string(assembly(&deps)).unwrap())
+}
+
+/// Builds the fixture for the host like [`build`], expecting the build to fail, and returns
+/// its error output.
+fn build_fails(name: &str, features: &[&str]) -> String {
+    let output = cargo(name, None, features)
+        .output()
+        .expect("failed to run cargo");
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(
+        !output.status.success(),
+        "fixture build succeeded:\n{stderr}"
+    );
+    stderr
+}
+
+fn cargo(name: &str, target: Option<&str>, features: &[&str]) -> Command {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut cargo = Command::new(env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+    cargo
+        .current_dir(root.join("tests/fixtures/algo"))
+        .args(["rustc", "--release", "--lib", "--quiet"])
+        .env("CARGO_TARGET_DIR", target_dir(name))
+        .env("RUSTFLAGS", "-D warnings")
+        .env_remove("CARGO_ENCODED_RUSTFLAGS");
+    if let Some(target) = target {
+        cargo.args(["--target", target]);
+    }
+    if !features.is_empty() {
+        let features: Vec<String> = features
+            .iter()
+            .map(|f| {
+                if f.contains('/') {
+                    f.to_string()
+                } else {
+                    format!("flashops/{f}")
+                }
+            })
+            .collect();
+        cargo.arg("--features").arg(features.join(","));
+    }
+    cargo.args(["--", "--emit", "asm,link"]);
+    cargo
+}
+
+fn target_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target/fixtures")
+        .join(name)
+}
+
+fn assembly(deps: &Path) -> PathBuf {
+    fs::read_dir(deps)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "s")
+                && path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("flashops_fixture")
+        })
+        .max_by_key(|path| fs::metadata(path).unwrap().modified().unwrap())
+        .expect("no assembly emitted")
+}
+
+fn installed(target: &str) -> bool {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    Command::new(rustc)
+        .args(["--print", "target-libdir", "--target", target])
+        .output()
+        .is_ok_and(|output| {
+            output.status.success()
+                && Path::new(String::from_utf8_lossy(&output.stdout).trim()).exists()
+        })
+}
+
+#[test]
+fn expansion_builds_under_deny_warnings() {
+    build("warnings", None, &[]);
+}
+
+#[test]
+fn stub_expansion_builds_under_deny_warnings() {
+    build("stubs", None, &["full-symbol-table"]);
+}
+
+#[test]
+fn erase_chip_and_verify_stubs_are_emitted_by_default() {
+    let asm = build("default-stubs", None, &[]).unwrap();
+    assert!(asm.contains("\nerase_chip:"), "{asm}");
+    assert!(asm.contains("\nverify:"), "{asm}");
+}
+
+#[test]
+fn no_unsupported_stubs_leaves_erase_chip_and_verify_out() {
+    let asm = build("no-stubs", None, &["no-unsupported-stubs"]).unwrap();
+    assert!(!asm.contains("\nerase_chip:"), "{asm}");
+    assert!(!asm.contains("\nverify:"), "{asm}");
+    assert!(asm.contains("\ninitialize:"), "{asm}");
+}
+
+#[test]
+fn no_checks_conflicts_with_the_checks_it_strips() {
+    for feature in ["bounds-check", "strict-erase", "strict-program"] {
+        let stderr = build_fails("no-checks-conflict", &["no-checks", feature]);
+        assert!(
+            stderr.contains(&format!(
+                "features `no-checks` and `{feature}` are mutually exclusive"
+            )),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn no_unsupported_stubs_conflicts_with_full_symbol_table() {
+    let stderr = build_fails(
+        "stubs-conflict",
+        &["no-unsupported-stubs", "full-symbol-table"],
+    );
+    assert!(
+        stderr.contains(
+            "features `no-unsupported-stubs` and `full-symbol-table` are mutually exclusive"
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn panic_udf_conflicts_with_panic_bkpt() {
+    let stderr = build_fails("panic-conflict", &["panic-udf", "panic-bkpt"]);
+    assert!(
+        stderr.contains("features `panic-udf` and `panic-bkpt` are mutually exclusive"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn verify_without_read_asks_for_custom_verify() {
+    let stderr = build_fails("verify-without-read", &["verify"]);
+    assert!(
+        stderr.contains("feature `verify` needs `read` for its default implementation"),
+        "{stderr}"
+    );
+    assert!(
+        !stderr.contains("not all trait items implemented"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn ecc_expansion_builds_under_deny_warnings() {
+    let asm = build("ecc", None, &["flashops-fixture/ecc"]).unwrap();
+    assert!(asm.contains("\nprogram_page:"), "{asm}");
+}
+
+#[test]
+fn ecc_size_above_max_ecc_size_is_rejected() {
+    let stderr = build_fails("ecc-too-large", &["flashops-fixture/oversized-ecc"]);
+    assert!(
+        stderr.contains("ecc_size must be at most MAX_ECC_SIZE"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn riscv32_places_entry_points_in_text_entry() {
+    let Some(asm) = build("riscv32", Some("riscv32imac-unknown-none-elf"), &[]) else {
+        return;
+    };
+    assert!(asm.contains(".section\t.text.entry"), "{asm}");
+    assert!(!asm.contains(".section\t.entry,"), "{asm}");
+    assert!(asm.contains("\ninitialize:"), "{asm}");
+}
+
+#[test]
+fn cortex_m_places_entry_points_in_entry() {
+    let Some(asm) = build("thumbv7em", Some("thumbv7em-none-eabi"), &[]) else {
+        return;
+    };
+    assert!(asm.contains(".section\t.entry,"), "{asm}");
+    assert!(asm.contains("\ninitialize:"), "{asm}");
+}
+
+#[test]
+fn defmt_formatting_builds_for_cortex_m() {
+    let Some(asm) = build("defmt", Some("thumbv7em-none-eabi"), &["defmt"]) else {
+        return;
+    };
+    assert!(asm.contains("\ninitialize:"), "{asm}");
+}
+
+#[test]
+fn trustzone_secure_places_entry_points_in_entry_secure() {
+    let target = "thumbv8m.main-none-eabi";
+    let Some(asm) = build("trustzone", Some(target), &["trustzone-secure"]) else {
+        return;
+    };
+    assert!(asm.contains(".section\t.entry.secure,"), "{asm}");
+    assert!(!asm.contains(".section\t.entry,"), "{asm}");
+    assert!(asm.contains("\ninitialize:"), "{asm}");
+}
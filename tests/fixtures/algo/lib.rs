@@ -0,0 +1,46 @@
+//! Minimal downstream algorithm crate, built by `tests/fixtures.rs`.
+
+#![no_std]
+#![deny(static_mut_refs)]
+
+use flashops::{flash_algorithm, Error, FlashOps, Operation};
+
+struct Algorithm;
+
+impl FlashOps for Algorithm {
+    fn create(_address: u32, _clock: u32, _operation: Operation) -> Result<Self, Error> {
+        Ok(Self)
+    }
+
+    fn erase_sector(&mut self, _address: u32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn program_page(&mut self, _address: u32, _data: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    #[cfg(feature = "ecc")]
+    fn program_page_ecc(&mut self, _address: u32, _data: &[u8], _ecc: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "oversized-ecc"))]
+flash_algorithm!(Algorithm, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+#[cfg(feature = "oversized-ecc")]
+flash_algorithm!(Algorithm, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}],
+    ecc_size: 257
+});
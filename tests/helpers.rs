@@ -0,0 +1,225 @@
+//! Tests of the free helpers, which need no generated algorithm.
+
+use flashops::{
+    copy_page, crc32, pages_in, read_u16_be, read_u16_le, read_u32_be, read_u32_le,
+    sector_aligned_base, sector_for_address, ErrorCode, FlashGeometry, FlashLog, LogBuffer,
+    WordOrder,
+};
+
+#[test]
+fn crc32_matches_the_standard_check_value() {
+    assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(0, b""), 0);
+}
+
+#[test]
+fn crc32_continues_from_a_previous_result() {
+    let crc = crc32(crc32(0, b"1234"), b"56789");
+    assert_eq!(crc, 0xCBF4_3926);
+}
+
+#[test]
+fn copy_page_takes_exactly_one_page() {
+    assert_eq!(copy_page::<4>(&[1, 2, 3, 4]), Ok([1, 2, 3, 4]));
+    for data in [&[1, 2, 3][..], &[1, 2, 3, 4, 5], &[]] {
+        assert_eq!(copy_page::<4>(data), Err(ErrorCode::InvalidArgument.into()));
+    }
+}
+
+const PATTERN: [u8; 6] = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC];
+
+#[test]
+fn readers_assemble_known_patterns() {
+    assert_eq!(read_u16_le(&PATTERN, 0), Some(0x3412));
+    assert_eq!(read_u16_be(&PATTERN, 0), Some(0x1234));
+    assert_eq!(read_u32_le(&PATTERN, 0), Some(0x7856_3412));
+    assert_eq!(read_u32_be(&PATTERN, 0), Some(0x1234_5678));
+    assert_eq!(read_u16_le(&PATTERN, 3), Some(0x9A78));
+    assert_eq!(read_u32_be(&PATTERN, 2), Some(0x5678_9ABC));
+}
+
+#[test]
+fn readers_reject_words_past_the_end() {
+    assert_eq!(read_u16_le(&PATTERN, 5), None);
+    assert_eq!(read_u32_be(&PATTERN, 3), None);
+    assert_eq!(read_u32_le(&PATTERN, 7), None);
+    assert_eq!(read_u16_be(&[], 0), None);
+}
+
+#[test]
+fn word_order_defaults_to_little_endian() {
+    assert_eq!(WordOrder::default(), WordOrder::LittleEndian);
+    assert_eq!(
+        WordOrder::LittleEndian.read_u32(&PATTERN, 1),
+        read_u32_le(&PATTERN, 1)
+    );
+    assert_eq!(
+        WordOrder::BigEndian.read_u32(&PATTERN, 1),
+        read_u32_be(&PATTERN, 1)
+    );
+    assert_eq!(WordOrder::BigEndian.read_u16(&PATTERN, 4), Some(0x9ABC));
+}
+
+#[test]
+fn pages_in_covers_a_sector_that_is_not_a_whole_number_of_pages() {
+    let pages = pages_in(0x0800_0000, 0x900, 0x400);
+    assert_eq!(pages.len(), 3);
+    assert_eq!(
+        pages.collect::<Vec<_>>(),
+        [0x0800_0000, 0x0800_0400, 0x0800_0800]
+    );
+    assert_eq!(pages_in(0x0800_0000, 0x1000, 0).count(), 0);
+}
+
+#[test]
+fn pages_in_stops_at_the_top_of_the_address_space() {
+    let pages = pages_in(0xFFFF_F000, 0x1000, 0x400);
+    assert_eq!(pages.len(), 4);
+    assert_eq!(pages.last(), Some(0xFFFF_FC00));
+
+    // A sector described as running past 2^32 must not wrap around to address 0.
+    let pages = pages_in(0xFFFF_FF00, 0x1000, 0x100);
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages.collect::<Vec<_>>(), [0xFFFF_FF00]);
+}
+
+/// STM32F4-style table: four 16 KiB, one 64 KiB and three 128 KiB sectors.
+struct Mixed;
+
+impl FlashGeometry for Mixed {
+    const FLASH_ADDRESS: u32 = 0x0800_0000;
+    const FLASH_SIZE: u32 = 0x8_0000;
+    const PAGE_SIZE: u32 = 0x400;
+    const EMPTY_VALUE: u8 = 0xFF;
+    const PROGRAM_TIMEOUT: u32 = 100;
+    const ERASE_TIMEOUT: u32 = 2000;
+    const SECTORS: &'static [(u32, u32)] =
+        &[(0x4000, 0x0), (0x1_0000, 0x1_0000), (0x2_0000, 0x2_0000)];
+}
+
+/// Two banks with a gap, as the `regions` form of `flash_algorithm!` describes them.
+struct Banked;
+
+impl FlashGeometry for Banked {
+    const FLASH_ADDRESS: u32 = 0x0800_0000;
+    const FLASH_SIZE: u32 = 0x10_1000;
+    const PAGE_SIZE: u32 = 0x100;
+    const EMPTY_VALUE: u8 = 0xFF;
+    const PROGRAM_TIMEOUT: u32 = 100;
+    const ERASE_TIMEOUT: u32 = 2000;
+    const SECTORS: &'static [(u32, u32)] = &[(0x800, 0x0), (0x1000, 0x10_0000)];
+    const REGIONS: &'static [(u32, u32)] = &[(0x0800_0000, 0x2000), (0x0810_0000, 0x1000)];
+}
+
+const _: () = assert!(matches!(
+    sector_aligned_base::<Mixed>(0x0801_2345),
+    Some(0x0801_0000)
+));
+
+#[test]
+fn sector_for_address_finds_each_entry_boundary() {
+    assert_eq!(
+        sector_for_address::<Mixed>(0x0800_0000),
+        Some((0x4000, 0x0800_0000))
+    );
+    assert_eq!(
+        sector_for_address::<Mixed>(0x0800_3FFF),
+        Some((0x4000, 0x0800_0000))
+    );
+    assert_eq!(
+        sector_for_address::<Mixed>(0x0800_4000),
+        Some((0x4000, 0x0800_4000))
+    );
+    assert_eq!(
+        sector_for_address::<Mixed>(0x0800_FFFF),
+        Some((0x4000, 0x0800_C000))
+    );
+    assert_eq!(
+        sector_for_address::<Mixed>(0x0801_0000),
+        Some((0x1_0000, 0x0801_0000))
+    );
+    assert_eq!(
+        sector_for_address::<Mixed>(0x0802_0000),
+        Some((0x2_0000, 0x0802_0000))
+    );
+}
+
+#[test]
+fn sector_for_address_covers_the_last_sector_and_nothing_past_it() {
+    assert_eq!(
+        sector_for_address::<Mixed>(0x0806_0000),
+        Some((0x2_0000, 0x0806_0000))
+    );
+    assert_eq!(
+        sector_for_address::<Mixed>(0x0807_FFFF),
+        Some((0x2_0000, 0x0806_0000))
+    );
+    assert_eq!(sector_for_address::<Mixed>(0x0808_0000), None);
+    assert_eq!(sector_for_address::<Mixed>(0x07FF_FFFF), None);
+    assert_eq!(sector_for_address::<Mixed>(u32::MAX), None);
+}
+
+#[test]
+fn sector_aligned_base_rounds_down_within_the_sector() {
+    assert_eq!(sector_aligned_base::<Mixed>(0x0800_4001), Some(0x0800_4000));
+    assert_eq!(sector_aligned_base::<Mixed>(0x0804_1000), Some(0x0804_0000));
+    assert_eq!(sector_aligned_base::<Mixed>(0x0808_0000), None);
+}
+
+#[test]
+fn lookups_skip_the_gap_between_banks() {
+    assert_eq!(
+        sector_for_address::<Banked>(0x0800_1FFF),
+        Some((0x800, 0x0800_1800))
+    );
+    assert_eq!(sector_for_address::<Banked>(0x0800_2000), None);
+    assert_eq!(sector_for_address::<Banked>(0x080F_FFFF), None);
+    assert_eq!(
+        sector_aligned_base::<Banked>(0x0810_0FFF),
+        Some(0x0810_0000)
+    );
+    assert_eq!(sector_aligned_base::<Banked>(0x0810_1000), None);
+    assert!(Banked::contains(0x0810_0000, 0x1000));
+    assert!(!Banked::contains(0x0800_1000, 0x2000));
+}
+
+/// Reads `buffer` the way the host does, through its `#[repr(C)]` layout: the count of bytes
+/// written, the capacity, then the data.
+fn log_contents<const N: usize>(buffer: &LogBuffer<N>) -> (u32, u32, [u8; N]) {
+    let base = (buffer as *const LogBuffer<N>).cast::<u8>();
+    unsafe {
+        (
+            base.cast::<u32>().read(),
+            base.add(4).cast::<u32>().read(),
+            base.add(8).cast::<[u8; N]>().read(),
+        )
+    }
+}
+
+#[test]
+fn log_buffer_appends_newline_terminated_messages() {
+    let buffer = LogBuffer::<16>::new();
+    buffer.write("erase");
+    buffer.write("ok");
+    let (written, capacity, data) = log_contents(&buffer);
+    assert_eq!((written, capacity), (9, 16));
+    assert_eq!(data[..9], *b"erase\nok\n");
+}
+
+#[test]
+fn log_buffer_wraps_around_keeping_the_newest_bytes() {
+    let buffer = LogBuffer::<8>::new();
+    buffer.write("program");
+    buffer.write("verify");
+    let (written, capacity, data) = log_contents(&buffer);
+    assert_eq!((written, capacity), (15, 8));
+    // "program\nverify\n": the last 8 bytes, starting at index 15 % 8.
+    let start = written as usize % data.len();
+    let newest: Vec<u8> = data[start..]
+        .iter()
+        .chain(&data[..start])
+        .copied()
+        .collect();
+    assert_eq!(newest, b"\nverify\n");
+    assert_eq!(data[(written as usize - 1) % data.len()], b'\n');
+}
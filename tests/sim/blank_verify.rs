@@ -0,0 +1,108 @@
+use flashops::error_codes::{INVALID_ARGUMENT, VERIFY_MISMATCH};
+use flashops::{flash_algorithm, FlashOps, VERIFY};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Blank;
+
+flash_algorithm!(Algo<Blank>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+/// Calls `verify` with a null buffer, asking for a blank check of the range.
+fn verify_blank(address: u32, size: u32) -> u32 {
+    unsafe { verify(address, size, core::ptr::null()) }
+}
+
+fn calls() -> Vec<Call> {
+    fixture::device().calls[1..].to_vec()
+}
+
+#[test]
+fn an_erased_range_is_blank() {
+    let _serial = fixture::start::<Algo<Blank>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    assert_eq!(verify_blank(0x0800_0010, 0x90), 0);
+    assert_eq!(
+        calls(),
+        [
+            Call::Read(0x0800_0010, 64),
+            Call::Read(0x0800_0050, 64),
+            Call::Read(0x0800_0090, 16)
+        ]
+    );
+}
+
+#[test]
+fn the_first_programmed_byte_fails_the_check() {
+    let _serial = fixture::start::<Algo<Blank>>(__deinit);
+    fixture::device()
+        .sim
+        .program_page(0x0800_0047, &[0xFE])
+        .unwrap();
+    assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    assert_eq!(verify_blank(0x0800_0000, 0x1000), VERIFY_MISMATCH.get());
+    assert_eq!(
+        calls(),
+        [Call::Read(0x0800_0000, 64), Call::Read(0x0800_0040, 64)]
+    );
+    assert_eq!(verify_blank(0x0800_0048, 0x100), 0);
+}
+
+#[test]
+fn a_buffer_of_empty_bytes_is_compared_like_any_data() {
+    let _serial = fixture::start::<Algo<Blank>>(__deinit);
+    fixture::device()
+        .sim
+        .program_page(0x0800_0000, &[0x00])
+        .unwrap();
+    assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    assert_eq!(__verify(0x0800_0000, &[0x00, 0xFF]), 0);
+    assert_eq!(verify_blank(0x0800_0000, 2), VERIFY_MISMATCH.get());
+}
+
+#[test]
+fn a_wrapping_range_is_rejected() {
+    let _serial = fixture::start::<Algo<Blank>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    assert_eq!(verify_blank(0xFFFF_FF00, 0x200), INVALID_ARGUMENT.get());
+    assert!(calls().is_empty());
+}
+
+mod pattern {
+    use flashops::error_codes::VERIFY_MISMATCH;
+    use flashops::{flash_algorithm, FlashOps, VERIFY};
+
+    use crate::fixture::{self, Algo};
+
+    pub struct Patterned;
+
+    flash_algorithm!(Algo<Patterned>, {
+        flash_address: 0x0800_0000,
+        flash_size: 0x4000,
+        page_size: 0x100,
+        empty_value: 0xFF,
+        sectors: [{size: 0x1000, address: 0x0}],
+        empty_pattern: [0xFF, 0x00]
+    });
+
+    fn verify_blank(address: u32, size: u32) -> u32 {
+        unsafe { verify(address, size, core::ptr::null()) }
+    }
+
+    #[test]
+    fn blank_bytes_follow_the_empty_pattern() {
+        let _serial = fixture::start::<Algo<Patterned>>(__deinit);
+        assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+        assert_eq!(verify_blank(0x0800_0001, 0x41), 0);
+        fixture::device()
+            .sim
+            .program_page(0x0800_0002, &[0x00])
+            .unwrap();
+        assert_eq!(verify_blank(0x0800_0000, 4), VERIFY_MISMATCH.get());
+    }
+}
@@ -0,0 +1,70 @@
+use flashops::error_codes::MISALIGNED_BUFFER;
+use flashops::{flash_algorithm, FlashOps, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Dma;
+
+flash_algorithm!(Algo<Dma>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}],
+    buffer_alignment: 4
+});
+
+/// Host buffer with a known alignment, so that offsets into it give any pointer alignment.
+#[repr(align(8))]
+struct Buffer([u8; 0x208]);
+
+fn calls() -> Vec<Call> {
+    fixture::device().calls[1..].to_vec()
+}
+
+#[test]
+fn the_alignment_is_reported_to_the_host() {
+    assert_eq!(FlashAlgorithmInfo.buffer_alignment, 4);
+}
+
+#[test]
+fn aligned_buffers_are_programmed() {
+    let _serial = fixture::start::<Algo<Dma>>(__deinit);
+    let buffer = Buffer([0x5A; 0x208]);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_page(0x0800_0000, &buffer.0[..0x100]), 0);
+    assert_eq!(__program_page(0x0800_0100, &buffer.0[4..0x104]), 0);
+    assert_eq!(__program_pages(0x0800_0200, &buffer.0[8..0x208]), 0);
+    assert_eq!(
+        calls(),
+        [
+            Call::ProgramPage(0x0800_0000, 0x100),
+            Call::ProgramPage(0x0800_0100, 0x100),
+            Call::ProgramPage(0x0800_0200, 0x100),
+            Call::ProgramPage(0x0800_0300, 0x100),
+        ]
+    );
+}
+
+#[test]
+fn misaligned_buffers_never_reach_the_algorithm() {
+    let _serial = fixture::start::<Algo<Dma>>(__deinit);
+    let buffer = Buffer([0x5A; 0x208]);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    for offset in 1..4 {
+        let data = &buffer.0[offset..offset + 0x100];
+        assert_eq!(__program_page(0x0800_0000, data), MISALIGNED_BUFFER.get());
+        assert_eq!(__program_pages(0x0800_0000, data), MISALIGNED_BUFFER.get());
+    }
+    let data = &buffer.0[2..0x102];
+    let mut count = 0;
+    assert_eq!(
+        unsafe { program_page_counted(0x0800_0000, 0x100, data.as_ptr(), &mut count) },
+        MISALIGNED_BUFFER.get()
+    );
+    assert_eq!(
+        unsafe { program_and_verify(0x0800_0000, 0x100, data.as_ptr()) },
+        MISALIGNED_BUFFER.get()
+    );
+    assert!(calls().is_empty());
+}
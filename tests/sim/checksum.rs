@@ -0,0 +1,109 @@
+use flashops::{crc32, error_codes, flash_algorithm, FlashOps, PROGRAM, VERIFY};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Checksums;
+
+flash_algorithm!(Algo<Checksums>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+fn program(address: u32, data: &[u8]) {
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_pages(address, data), 0);
+    assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    fixture::device().calls.clear();
+}
+
+#[test]
+fn verify_crc_compares_against_the_image_crc() {
+    let _serial = fixture::start::<Algo<Checksums>>(__deinit);
+    let image: Vec<u8> = (0..0x200).map(|i| (i * 7) as u8).collect();
+    program(0x0800_0100, &image);
+
+    let crc = crc32(0, &image);
+    assert_eq!(unsafe { verify_crc(0x0800_0100, 0x200, crc) }, 0);
+    assert_eq!(
+        unsafe { verify_crc(0x0800_0100, 0x200, crc ^ 1) },
+        error_codes::VERIFY_MISMATCH.get()
+    );
+    assert_eq!(
+        fixture::device().calls,
+        [
+            Call::Checksum(0x0800_0100, 0x200),
+            Call::Checksum(0x0800_0100, 0x200)
+        ]
+    );
+}
+
+/// CRCs of the pages starting at `address`, as a host would compute them from its image.
+fn page_crcs(address: u32, count: u32) -> Vec<u32> {
+    let memory = fixture::device().sim.memory().to_vec();
+    let start = (address - 0x0800_0000) as usize;
+    memory[start..start + count as usize * 0x100]
+        .chunks(0x100)
+        .map(|page| crc32(0, page))
+        .collect()
+}
+
+#[test]
+fn verify_pages_reports_the_first_page_that_differs() {
+    let _serial = fixture::start::<Algo<Checksums>>(__deinit);
+    let image: Vec<u8> = (0..0x400).map(|i| (i * 3) as u8).collect();
+    program(0x0800_0000, &image);
+    let mut crcs = page_crcs(0x0800_0000, 4);
+
+    let mut failed = 0xDEAD_BEEF;
+    assert_eq!(
+        unsafe { verify_pages(0x0800_0000, 4, crcs.as_ptr(), &mut failed) },
+        0
+    );
+    assert_eq!(failed, 0xDEAD_BEEF);
+
+    crcs[1] ^= 1;
+    crcs[3] ^= 1;
+    assert_eq!(
+        unsafe { verify_pages(0x0800_0000, 4, crcs.as_ptr(), &mut failed) },
+        error_codes::VERIFY_MISMATCH.get()
+    );
+    assert_eq!(failed, 1);
+    assert_eq!(
+        fixture::device().calls[4..],
+        [
+            Call::Checksum(0x0800_0000, 0x100),
+            Call::Checksum(0x0800_0100, 0x100)
+        ]
+    );
+}
+
+#[test]
+fn verify_pages_rejects_null_pointers() {
+    let _serial = fixture::start::<Algo<Checksums>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    let crcs = page_crcs(0x0800_0000, 2);
+    let mut failed = 0;
+    assert_eq!(
+        unsafe { verify_pages(0x0800_0000, 2, crcs.as_ptr(), core::ptr::null_mut()) },
+        error_codes::INVALID_ARGUMENT.get()
+    );
+    assert_eq!(
+        unsafe { verify_pages(0x0800_0000, 2, core::ptr::null(), &mut failed) },
+        error_codes::INVALID_ARGUMENT.get()
+    );
+    assert_eq!(fixture::device().calls[1..], []);
+}
+
+#[test]
+fn checksum_rejects_a_null_result_pointer() {
+    let _serial = fixture::start::<Algo<Checksums>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    assert_eq!(
+        unsafe { checksum(0x0800_0000, 0x100, core::ptr::null_mut()) },
+        error_codes::INVALID_ARGUMENT.get()
+    );
+    assert_eq!(fixture::device().calls[1..], []);
+}
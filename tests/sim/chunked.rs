@@ -0,0 +1,80 @@
+use flashops::error_codes::ADDRESS_OUT_OF_RANGE;
+use flashops::{flash_algorithm, program_chunked, FlashGeometry, FlashOps, Operation};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Chunked;
+
+flash_algorithm!(Algo<Chunked>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}],
+    write_chunk: 0x40
+});
+
+/// A flash whose last chunk ends at the top of the address space.
+pub struct Top;
+
+impl FlashGeometry for Algo<Top> {
+    const FLASH_ADDRESS: u32 = 0xFFFF_FF00;
+    const FLASH_SIZE: u32 = 0x100;
+    const PAGE_SIZE: u32 = 0x100;
+    const EMPTY_VALUE: u8 = 0xFF;
+    const PROGRAM_TIMEOUT: u32 = 100;
+    const ERASE_TIMEOUT: u32 = 100;
+    const SECTORS: &'static [(u32, u32)] = &[(0x100, 0x0)];
+    const WRITE_CHUNK: u32 = 0x40;
+}
+
+fn calls() -> Vec<Call> {
+    fixture::device().calls[1..].to_vec()
+}
+
+#[test]
+fn a_page_is_split_at_chunk_boundaries() {
+    let _serial = fixture::start::<Algo<Chunked>>(__deinit);
+    let mut algo = Algo::<Chunked>::create(0x0800_0000, 0, Operation::Program).unwrap();
+    let data: Vec<u8> = (0..0xA0).collect();
+    assert_eq!(program_chunked(&mut algo, 0x0800_0130, &data), Ok(()));
+    assert_eq!(
+        calls(),
+        [
+            Call::ProgramChunk(0x0800_0130, 0x10),
+            Call::ProgramChunk(0x0800_0140, 0x40),
+            Call::ProgramChunk(0x0800_0180, 0x40),
+            Call::ProgramChunk(0x0800_01C0, 0x10),
+        ]
+    );
+    assert_eq!(fixture::device().sim.memory()[0x130..0x1D0], data);
+}
+
+#[test]
+fn an_address_below_the_flash_is_rejected() {
+    let _serial = fixture::start::<Algo<Chunked>>(__deinit);
+    let mut algo = Algo::<Chunked>::create(0x0800_0000, 0, Operation::Program).unwrap();
+    assert_eq!(
+        program_chunked(&mut algo, 0x07FF_FFF0, &[0; 0x20]),
+        Err(ADDRESS_OUT_OF_RANGE)
+    );
+    assert!(calls().is_empty());
+}
+
+#[test]
+fn chunks_reach_the_top_of_the_address_space() {
+    let _serial = fixture::start::<Algo<Top>>(|| 0);
+    let mut algo = Algo::<Top>::create(0xFFFF_FF00, 0, Operation::Program).unwrap();
+    assert_eq!(program_chunked(&mut algo, 0xFFFF_FFB0, &[0; 0x50]), Ok(()));
+    assert_eq!(
+        calls(),
+        [
+            Call::ProgramChunk(0xFFFF_FFB0, 0x10),
+            Call::ProgramChunk(0xFFFF_FFC0, 0x40),
+        ]
+    );
+    assert_eq!(
+        program_chunked(&mut algo, 0xFFFF_FFF0, &[0; 0x20]),
+        Err(ADDRESS_OUT_OF_RANGE)
+    );
+}
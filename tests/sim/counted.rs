@@ -0,0 +1,61 @@
+use flashops::error_codes::{ADDRESS_OUT_OF_RANGE, PROGRAM_FAILED};
+use flashops::{flash_algorithm, FlashOps, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Counted;
+
+flash_algorithm!(Algo<Counted>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+/// Calls `program_page_counted`, returning its result and the count it wrote.
+fn program(address: u32, data: &[u8]) -> (u32, u32) {
+    let mut count = 0xDEAD_BEEF;
+    let result =
+        unsafe { program_page_counted(address, data.len() as u32, data.as_ptr(), &mut count) };
+    (result, count)
+}
+
+#[test]
+fn a_full_program_reports_every_byte() {
+    let _serial = fixture::start::<Algo<Counted>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(program(0x0800_0000, &[0x5A; 0x100]), (0, 0x100));
+    assert_eq!(program(0x0800_0100, &[]), (0, 0));
+    assert_eq!(
+        fixture::device().calls[1..],
+        [Call::ProgramPage(0x0800_0000, 0x100)]
+    );
+}
+
+#[test]
+fn a_partial_count_leaves_the_rest_unprogrammed() {
+    let _serial = fixture::start::<Algo<Counted>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    fixture::device().counted_limit = Some(0x60);
+    assert_eq!(program(0x0800_0000, &[0x5A; 0x100]), (0, 0x60));
+    let device = fixture::device();
+    assert!(device.sim.memory()[..0x60].iter().all(|&byte| byte == 0x5A));
+    assert!(device.sim.memory()[0x60..].iter().all(|&byte| byte == 0xFF));
+    assert_eq!(device.calls[1..], [Call::ProgramPage(0x0800_0000, 0x60)]);
+}
+
+#[test]
+fn a_failed_program_leaves_the_count_untouched() {
+    let _serial = fixture::start::<Algo<Counted>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(program(0x0800_0000, &[0; 0x10]), (0, 0x10));
+    assert_eq!(
+        program(0x0800_0000, &[0; 0x10]),
+        (PROGRAM_FAILED.get(), 0xDEAD_BEEF)
+    );
+    assert_eq!(
+        program(0x0800_4000, &[0; 0x10]),
+        (ADDRESS_OUT_OF_RANGE.get(), 0xDEAD_BEEF)
+    );
+}
@@ -0,0 +1,81 @@
+use flashops::error_codes::{ADDRESS_OUT_OF_RANGE, UNALIGNED_PROGRAM};
+use flashops::{flash_algorithm, FlashOps, ERASE, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct DataFlash;
+
+flash_algorithm!(Algo<DataFlash>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}],
+    data_region: {
+        address: 0x0808_0000,
+        size: 0x1000,
+        page_size: 0x40,
+        program_granularity: 4
+    }
+});
+
+fn calls() -> Vec<Call> {
+    fixture::device().calls[1..].to_vec()
+}
+
+#[test]
+fn erases_inside_the_region_go_to_erase_data() {
+    let _serial = fixture::start::<Algo<DataFlash>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__erase_sector(0x0808_0040), 0);
+    assert_eq!(__erase_sector(0x0808_0FFF), 0);
+    assert_eq!(__erase_sector(0x0800_1000), 0);
+    assert_eq!(__erase_sector(0x0808_1000), ADDRESS_OUT_OF_RANGE.get());
+    assert_eq!(
+        calls(),
+        [
+            Call::EraseData(0x0808_0040),
+            Call::EraseData(0x0808_0FFF),
+            Call::EraseSector(0x0800_1000),
+        ]
+    );
+}
+
+#[test]
+fn programs_inside_the_region_go_to_program_data() {
+    let _serial = fixture::start::<Algo<DataFlash>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_page(0x0808_0040, &[0; 0x40]), 0);
+    assert_eq!(__program_page(0x0808_0FC0, &[0; 4]), 0);
+    assert_eq!(__program_page(0x0800_0000, &[0; 0x100]), 0);
+    assert_eq!(
+        calls(),
+        [
+            Call::ProgramData(0x0808_0040, 0x40),
+            Call::ProgramData(0x0808_0FC0, 4),
+            Call::ProgramPage(0x0800_0000, 0x100),
+        ]
+    );
+}
+
+#[test]
+fn programs_are_checked_against_the_region_geometry() {
+    let _serial = fixture::start::<Algo<DataFlash>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    // Crosses a 0x40-byte data page, which a 0x100-byte main page would allow.
+    assert_eq!(
+        __program_page(0x0808_0020, &[0; 0x40]),
+        UNALIGNED_PROGRAM.get()
+    );
+    // Not a multiple of the region's program_granularity.
+    assert_eq!(
+        __program_page(0x0808_0000, &[0; 6]),
+        UNALIGNED_PROGRAM.get()
+    );
+    // Runs past the end of the region, so it is treated as a main-flash request.
+    assert_eq!(
+        __program_page(0x0808_0FF0, &[0; 0x20]),
+        ADDRESS_OUT_OF_RANGE.get()
+    );
+    assert!(calls().is_empty());
+}
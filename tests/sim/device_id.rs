@@ -0,0 +1,72 @@
+use flashops::error_codes::NOT_INITIALIZED;
+use flashops::{flash_algorithm, FlashOps, ERASE};
+
+use crate::fixture::{self, Algo};
+
+pub struct DeviceId;
+
+flash_algorithm!(Algo<DeviceId>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+#[test]
+fn read_device_id_reports_the_part() {
+    let _serial = fixture::start::<Algo<DeviceId>>(__deinit);
+    fixture::reconfigure(|sim| sim.with_device_id(0x00EF_4018));
+    let mut id = 0;
+    assert_eq!(unsafe { read_device_id(&mut id) }, NOT_INITIALIZED.get());
+    assert_eq!(id, 0);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(unsafe { read_device_id(&mut id) }, 0);
+    assert_eq!(id, 0x00EF_4018);
+}
+
+mod allowed {
+    use flashops::error_codes::WRONG_DEVICE;
+    use flashops::{flash_algorithm, FlashOps, ERASE, VERIFY};
+
+    use crate::fixture::{self, Algo, Call};
+
+    pub struct Allowed;
+
+    flash_algorithm!(Algo<Allowed>, {
+        flash_address: 0x0800_0000,
+        flash_size: 0x4000,
+        page_size: 0x100,
+        empty_value: 0xFF,
+        sectors: [{size: 0x1000, address: 0x0}],
+        allowed_ids: [0x00EF_4017, 0x00EF_4018]
+    });
+
+    #[test]
+    fn an_allowed_id_initializes() {
+        let _serial = fixture::start::<Algo<Allowed>>(__deinit);
+        fixture::reconfigure(|sim| sim.with_device_id(0x00EF_4018));
+        assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+        assert_eq!(__erase_sector(0x0800_0000), 0);
+    }
+
+    #[test]
+    fn another_id_is_rejected_and_dropped() {
+        let _serial = fixture::start::<Algo<Allowed>>(__deinit);
+        fixture::reconfigure(|sim| sim.with_device_id(0x00C2_2018));
+        assert_eq!(__init(0x0800_0000, 0, ERASE), WRONG_DEVICE.get());
+        assert_eq!(fixture::device().drops, 1);
+        assert_eq!(__erase_sector(0x0800_0000), super::NOT_INITIALIZED.get());
+        assert_eq!(
+            fixture::device().calls,
+            [Call::Create(flashops::Operation::Erase)]
+        );
+    }
+
+    #[test]
+    fn verify_skips_the_check() {
+        let _serial = fixture::start::<Algo<Allowed>>(__deinit);
+        fixture::reconfigure(|sim| sim.with_device_id(0x00C2_2018));
+        assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    }
+}
@@ -0,0 +1,59 @@
+use flashops::error_codes::{ADDRESS_OUT_OF_RANGE, NOT_INITIALIZED};
+use flashops::{flash_algorithm, FlashOps, ERASE};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct EraseIfNeeded;
+
+flash_algorithm!(Algo<EraseIfNeeded>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+/// Calls `erase_if_needed`, returning its result and the flag it wrote.
+fn erase(address: u32) -> (u32, u32) {
+    let mut erased = 0xDEAD_BEEF;
+    let result = unsafe { erase_if_needed(address, &mut erased) };
+    (result, erased)
+}
+
+#[test]
+fn a_blank_sector_is_left_alone() {
+    let _serial = fixture::start::<Algo<EraseIfNeeded>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(erase(0x0800_1000), (0, 0));
+    let device = fixture::device();
+    assert_eq!(device.calls[1..].len(), 0x1000 / 64);
+    assert!(device.calls[1..]
+        .iter()
+        .all(|call| matches!(call, Call::Read(..))));
+}
+
+#[test]
+fn a_programmed_sector_is_erased() {
+    let _serial = fixture::start::<Algo<EraseIfNeeded>>(__deinit);
+    assert_eq!(
+        fixture::device().sim.program_page(0x0800_1FFF, &[0x00]),
+        Ok(())
+    );
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(erase(0x0800_1000), (0, 1));
+    let device = fixture::device();
+    assert_eq!(device.calls.last(), Some(&Call::EraseSector(0x0800_1000)));
+    assert!(device.sim.memory().iter().all(|&byte| byte == 0xFF));
+}
+
+#[test]
+fn a_rejected_request_leaves_the_flag_untouched() {
+    let _serial = fixture::start::<Algo<EraseIfNeeded>>(__deinit);
+    assert_eq!(erase(0x0800_1000), (NOT_INITIALIZED.get(), 0xDEAD_BEEF));
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(
+        erase(0x0800_4000),
+        (ADDRESS_OUT_OF_RANGE.get(), 0xDEAD_BEEF)
+    );
+    assert!(fixture::device().calls[1..].is_empty());
+}
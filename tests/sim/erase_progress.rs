@@ -0,0 +1,59 @@
+use core::sync::atomic::Ordering;
+
+use flashops::{flash_algorithm, FlashGeometry, FlashOps, ERASE};
+
+use crate::fixture::{self, Algo};
+
+pub struct Progress;
+
+flash_algorithm!(Algo<Progress>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+fn progress() -> u32 {
+    FlashEraseProgress.load(Ordering::Relaxed)
+}
+
+#[test]
+fn the_geometry_points_at_the_exported_counter() {
+    let counter = <Algo<Progress>>::erase_progress().unwrap();
+    assert!(core::ptr::eq(counter, &FlashEraseProgress));
+}
+
+#[test]
+fn erase_chip_counts_every_sector_from_zero() {
+    let _serial = fixture::start::<Algo<Progress>>(__deinit);
+    FlashEraseProgress.store(7, Ordering::Relaxed);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__erase_chip(), 0);
+    assert_eq!(progress(), 4);
+}
+
+#[test]
+fn erase_range_counts_the_sectors_it_erases() {
+    let _serial = fixture::start::<Algo<Progress>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__erase_chip(), 0);
+    assert_eq!(unsafe { erase_range(0x0800_0800, 0x1000) }, 0);
+    assert_eq!(progress(), 2);
+    assert_eq!(__erase_sector(0x0800_3000), 0);
+    assert_eq!(progress(), 2);
+}
+
+#[test]
+fn erase_chip_step_grows_one_sector_per_call() {
+    let _serial = fixture::start::<Algo<Progress>>(__deinit);
+    FlashEraseProgress.store(7, Ordering::Relaxed);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    let mut done = 0;
+    let mut seen = Vec::new();
+    while done == 0 {
+        assert_eq!(unsafe { erase_chip_step(&mut done) }, 0);
+        seen.push(progress());
+    }
+    assert_eq!(seen, [1, 2, 3, 4]);
+}
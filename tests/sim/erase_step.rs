@@ -0,0 +1,91 @@
+use flashops::error_codes::{ERASE_FAILED, NOT_INITIALIZED};
+use flashops::{flash_algorithm, FlashOps, ERASE, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Stepwise;
+
+flash_algorithm!(Algo<Stepwise>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+const BASES: [u32; 4] = [0x0800_0000, 0x0800_1000, 0x0800_2000, 0x0800_3000];
+
+/// Runs one `erase_chip_step` call, returning its result and the `done` flag it wrote.
+fn step() -> (u32, u32) {
+    let mut done = 0xDEAD_BEEF;
+    let result = unsafe { erase_chip_step(&mut done) };
+    (result, done)
+}
+
+fn erases() -> Vec<Call> {
+    fixture::device()
+        .calls
+        .iter()
+        .copied()
+        .filter(|call| matches!(call, Call::EraseSector(_)))
+        .collect()
+}
+
+#[test]
+fn each_call_erases_one_sector_until_done() {
+    let _serial = fixture::start::<Algo<Stepwise>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_page(0x0800_3000, &[0; 0x10]), 0);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(step(), (0, 0));
+    assert_eq!(step(), (0, 0));
+    assert_eq!(step(), (0, 0));
+    assert_eq!(step(), (0, 1));
+    assert_eq!(erases(), BASES.map(Call::EraseSector));
+    assert!(fixture::device()
+        .sim
+        .memory()
+        .iter()
+        .all(|&byte| byte == 0xFF));
+}
+
+#[test]
+fn a_finished_erase_starts_over() {
+    let _serial = fixture::start::<Algo<Stepwise>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    while step() == (0, 0) {}
+    assert_eq!(step(), (0, 0));
+    assert_eq!(erases().last(), Some(&Call::EraseSector(0x0800_0000)));
+}
+
+#[test]
+fn an_error_or_initialize_abandons_the_erase() {
+    let _serial = fixture::start::<Algo<Stepwise>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    fixture::device().failing_sector = Some(0x0800_1000);
+    assert_eq!(step(), (0, 0));
+    assert_eq!(step(), (ERASE_FAILED.get(), 0xDEAD_BEEF));
+    fixture::device().failing_sector = None;
+    assert_eq!(step(), (0, 0));
+    assert_eq!(step(), (0, 0));
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(step(), (0, 0));
+    assert_eq!(
+        erases(),
+        [
+            0x0800_0000,
+            0x0800_1000,
+            0x0800_0000,
+            0x0800_1000,
+            0x0800_0000
+        ]
+        .map(Call::EraseSector)
+    );
+}
+
+#[test]
+fn steps_need_an_initialized_algorithm() {
+    let _serial = fixture::start::<Algo<Stepwise>>(__deinit);
+    assert_eq!(step(), (NOT_INITIALIZED.get(), 0xDEAD_BEEF));
+    assert!(fixture::device().calls.is_empty());
+}
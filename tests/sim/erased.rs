@@ -0,0 +1,58 @@
+use flashops::{flash_algorithm, ErrorCode, FlashOps, Operation};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Erased;
+
+flash_algorithm!(Algo<Erased>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+fn algo() -> Algo<Erased> {
+    Algo::create(0x0800_0000, 0, Operation::Erase).unwrap()
+}
+
+#[test]
+fn default_reads_the_whole_sector_back() {
+    let _serial = fixture::start::<Algo<Erased>>(__deinit);
+    assert_eq!(algo().is_sector_erased(0x0800_1800), Ok(true));
+    let reads: Vec<Call> = fixture::device().calls[1..].to_vec();
+    assert_eq!(reads.len(), 0x1000 / 64);
+    assert_eq!(reads.first(), Some(&Call::Read(0x0800_1000, 64)));
+    assert_eq!(reads.last(), Some(&Call::Read(0x0800_1FC0, 64)));
+}
+
+#[test]
+fn default_finds_programmed_bytes_anywhere_in_the_sector() {
+    let _serial = fixture::start::<Algo<Erased>>(__deinit);
+    assert_eq!(algo().program_page(0x0800_1FFF, &[0xFE]), Ok(()));
+    assert_eq!(algo().is_sector_erased(0x0800_1000), Ok(false));
+    assert_eq!(algo().is_sector_erased(0x0800_1FFF), Ok(false));
+    assert_eq!(algo().is_sector_erased(0x0800_2000), Ok(true));
+    assert_eq!(algo().is_sector_erased(0x0800_0FFF), Ok(true));
+}
+
+#[test]
+fn default_stops_at_the_first_programmed_chunk() {
+    let _serial = fixture::start::<Algo<Erased>>(__deinit);
+    assert_eq!(algo().program_page(0x0800_0040, &[0x00; 4]), Ok(()));
+    fixture::device().calls.clear();
+    assert_eq!(algo().is_sector_erased(0x0800_0000), Ok(false));
+    assert_eq!(
+        fixture::device().calls[1..],
+        [Call::Read(0x0800_0000, 64), Call::Read(0x0800_0040, 64)]
+    );
+}
+
+#[test]
+fn default_rejects_addresses_outside_the_flash() {
+    let _serial = fixture::start::<Algo<Erased>>(__deinit);
+    assert_eq!(
+        algo().is_sector_erased(0x0800_4000),
+        Err(ErrorCode::AddressOutOfRange.into())
+    );
+}
@@ -0,0 +1,252 @@
+//! `FlashOps` implementation over a simulated part shared by the whole suite.
+//!
+//! Each test module declares its own marker type and invokes `flash_algorithm!` for
+//! `Algo<Marker>`, so the geometry comes from the invocation while every algorithm forwards to
+//! the single [`Device`] that [`start`] resets. Tests run one at a time under [`start`]'s lock.
+
+use std::marker::PhantomData;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use flashops::{Error, ErrorCode, FlashGeometry, FlashOps, FlashSim, Operation, CONFIG_VOLATILE};
+
+/// A call into the algorithm, as recorded in [`Device::calls`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Call {
+    Create(Operation),
+    EraseSector(u32),
+    ProgramPage(u32, usize),
+    Read(u32, usize),
+    BlankCheck(u32, u32),
+    Checksum(u32, u32),
+    EraseData(u32),
+    ProgramData(u32, usize),
+    ProgramChunk(u32, usize),
+    WriteConfig(u32, u32),
+    /// The lifecycle hooks, only recorded while [`Device::trace_hooks`] is set.
+    Unlock,
+    Lock,
+    Uninit(Operation),
+    Deinit,
+}
+
+/// Error returned by a `create` that [`Device::failing_creates`] makes fail.
+pub const CREATE_FAILED: Error = Error::new(0x0C).unwrap();
+
+/// The simulated part, which outlives the algorithm instances created by `initialize`.
+pub struct Device {
+    pub sim: FlashSim,
+    pub calls: Vec<Call>,
+    /// Number of upcoming `create` calls that fail with [`CREATE_FAILED`].
+    pub failing_creates: u32,
+    /// Number of algorithm instances dropped since [`start`].
+    pub drops: u32,
+    /// Records `unlock`, `lock`, `uninit` and `deinit` in [`Device::calls`].
+    pub trace_hooks: bool,
+    /// A hook, as its [`Call`], that fails with the given error.
+    pub failing_hook: Option<(Call, Error)>,
+    /// Address of a byte that programs inverted, as a worn cell would.
+    pub weak_byte: Option<u32>,
+    /// Address of a sector whose erase fails with [`ErrorCode::EraseFailed`].
+    pub failing_sector: Option<u32>,
+    /// Most bytes a `program_page_counted` call consumes before returning its count.
+    pub counted_limit: Option<usize>,
+}
+
+static SERIAL: Mutex<()> = Mutex::new(());
+static DEVICE: Mutex<Option<Device>> = Mutex::new(None);
+
+/// Serializes the calling test and gives it an erased part with the geometry of `A`.
+///
+/// `deinit` is the `__deinit` wrapper of the module, called first so that an instance left
+/// behind by an earlier test does not leak into this one.
+pub fn start<A: FlashGeometry>(deinit: fn() -> u32) -> MutexGuard<'static, ()> {
+    let serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+    *lock() = Some(Device {
+        sim: FlashSim::new(
+            A::FLASH_ADDRESS,
+            A::FLASH_SIZE,
+            A::PAGE_SIZE,
+            A::EMPTY_VALUE,
+            A::SECTORS,
+        )
+        .with_empty_pattern(A::EMPTY_PATTERN),
+        calls: Vec::new(),
+        failing_creates: 0,
+        drops: 0,
+        trace_hooks: false,
+        failing_hook: None,
+        weak_byte: None,
+        failing_sector: None,
+        counted_limit: None,
+    });
+    deinit();
+    let mut device = device();
+    device.calls.clear();
+    device.drops = 0;
+    drop(device);
+    serial
+}
+
+/// Replaces the part of the running test with `f` applied to it, for the `FlashSim` builder
+/// methods.
+pub fn reconfigure(f: impl FnOnce(FlashSim) -> FlashSim) {
+    let mut device = device();
+    let sim = std::mem::replace(&mut device.sim, FlashSim::new(0, 0, 1, 0, &[]));
+    device.sim = f(sim);
+}
+
+/// The part of the running test.
+pub fn device() -> impl std::ops::DerefMut<Target = Device> {
+    struct Guard(MutexGuard<'static, Option<Device>>);
+
+    impl std::ops::Deref for Guard {
+        type Target = Device;
+
+        fn deref(&self) -> &Device {
+            self.0.as_ref().expect("test did not call fixture::start")
+        }
+    }
+
+    impl std::ops::DerefMut for Guard {
+        fn deref_mut(&mut self) -> &mut Device {
+            self.0.as_mut().expect("test did not call fixture::start")
+        }
+    }
+
+    Guard(lock())
+}
+
+fn lock() -> MutexGuard<'static, Option<Device>> {
+    DEVICE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Records a lifecycle hook and returns its result.
+fn hook(call: Call) -> Result<(), Error> {
+    let mut device = device();
+    if device.trace_hooks {
+        device.calls.push(call);
+    }
+    match device.failing_hook {
+        Some((failing, error)) if failing == call => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Algorithm of the test module whose marker type is `M`.
+pub struct Algo<M>(PhantomData<M>);
+
+impl<M> Drop for Algo<M> {
+    fn drop(&mut self) {
+        device().drops += 1;
+    }
+}
+
+impl<M> FlashOps for Algo<M> {
+    fn create(_address: u32, _clock: u32, operation: Operation) -> Result<Self, Error> {
+        let mut device = device();
+        device.calls.push(Call::Create(operation));
+        if device.failing_creates > 0 {
+            device.failing_creates -= 1;
+            return Err(CREATE_FAILED);
+        }
+        Ok(Self(PhantomData))
+    }
+
+    fn unlock(&mut self) -> Result<(), Error> {
+        hook(Call::Unlock)
+    }
+
+    fn lock(&mut self) -> Result<(), Error> {
+        hook(Call::Lock)
+    }
+
+    fn uninit(&mut self, operation: Operation) -> Result<(), Error> {
+        hook(Call::Uninit(operation))
+    }
+
+    fn deinit(&mut self) -> Result<(), Error> {
+        hook(Call::Deinit)
+    }
+
+    fn erase_sector(&mut self, address: u32) -> Result<(), Error> {
+        let mut device = device();
+        device.calls.push(Call::EraseSector(address));
+        if device.failing_sector == Some(address) {
+            return Err(ErrorCode::EraseFailed.into());
+        }
+        device.sim.erase_sector(address)
+    }
+
+    fn program_page(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        let mut device = device();
+        device.calls.push(Call::ProgramPage(address, data.len()));
+        let mut data = data.to_vec();
+        if let Some(weak) = device.weak_byte {
+            if let Some(byte) = data.get_mut(weak.wrapping_sub(address) as usize) {
+                *byte = !*byte;
+            }
+        }
+        device.sim.program_page(address, &data)
+    }
+
+    fn program_page_counted(&mut self, address: u32, data: &[u8]) -> Result<u32, Error> {
+        let limit = device().counted_limit.unwrap_or(data.len());
+        let data = &data[..data.len().min(limit)];
+        self.program_page(address, data)?;
+        Ok(data.len() as u32)
+    }
+
+    fn program_chunk(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        let mut device = device();
+        device.calls.push(Call::ProgramChunk(address, data.len()));
+        device.sim.program_page(address, data)
+    }
+
+    /// The part only models the main flash, so `data_region` requests are recorded and
+    /// succeed.
+    fn erase_data(&mut self, address: u32) -> Result<(), Error> {
+        device().calls.push(Call::EraseData(address));
+        Ok(())
+    }
+
+    fn program_data(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        device().calls.push(Call::ProgramData(address, data.len()));
+        Ok(())
+    }
+
+    fn read(&mut self, address: u32, data: &mut [u8]) -> Result<(), Error> {
+        let mut device = device();
+        device.calls.push(Call::Read(address, data.len()));
+        device.sim.read(address, data)
+    }
+
+    fn blank_check(&mut self, address: u32, size: u32) -> Result<(), Error> {
+        let mut device = device();
+        device.calls.push(Call::BlankCheck(address, size));
+        match device.sim.verify(address, size, None) {
+            Err(error) if error == ErrorCode::VerifyMismatch.into() => {
+                Err(ErrorCode::NotBlank.into())
+            }
+            result => result,
+        }
+    }
+
+    fn checksum(&mut self, address: u32, size: u32) -> Result<u32, Error> {
+        let mut device = device();
+        device.calls.push(Call::Checksum(address, size));
+        device.sim.checksum(address, size)
+    }
+
+    fn read_device_id(&mut self) -> Result<u32, Error> {
+        device().sim.read_device_id()
+    }
+
+    /// Accepts the two SPI NOR status registers, written with `0x01` and `0x31`.
+    fn write_config(&mut self, reg: u32, value: u32) -> Result<(), Error> {
+        device().calls.push(Call::WriteConfig(reg, value));
+        match reg & !CONFIG_VOLATILE {
+            0x01 | 0x31 => Ok(()),
+            _ => Err(ErrorCode::InvalidArgument.into()),
+        }
+    }
+}
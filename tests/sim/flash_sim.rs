@@ -0,0 +1,91 @@
+use flashops::{ErrorCode, FlashSim};
+
+fn sim() -> FlashSim {
+    FlashSim::new(0x1000, 0x400, 0x100, 0xFF, &[(0x100, 0x0), (0x200, 0x200)])
+}
+
+#[test]
+fn erase_restores_the_empty_value_of_one_sector() {
+    let mut sim = sim();
+    sim.program_page(0x1000, &[0x11; 0x100]).unwrap();
+    sim.program_page(0x1100, &[0x22; 0x100]).unwrap();
+    sim.erase_sector(0x10F0).unwrap();
+    assert!(sim.memory()[..0x100].iter().all(|&byte| byte == 0xFF));
+    assert!(sim.memory()[0x100..0x200].iter().all(|&byte| byte == 0x22));
+    assert_eq!(sim.sector(0x1300), Some((0x1200, 0x200)));
+}
+
+#[test]
+fn program_needs_erased_bytes_within_one_page() {
+    let mut sim = sim();
+    sim.program_page(0x1010, &[0x00; 0x10]).unwrap();
+    assert_eq!(
+        sim.program_page(0x1010, &[0x00; 0x10]),
+        Err(ErrorCode::ProgramFailed.into())
+    );
+    assert_eq!(
+        sim.program_page(0x10F0, &[0x00; 0x20]),
+        Err(ErrorCode::ProgramFailed.into())
+    );
+    assert_eq!(
+        sim.program_page(0x1400, &[0x00; 1]),
+        Err(ErrorCode::AddressOutOfRange.into())
+    );
+    assert_eq!(
+        sim.program_page(0x0FFF, &[0x00; 1]),
+        Err(ErrorCode::AddressOutOfRange.into())
+    );
+}
+
+#[test]
+fn verify_detects_mismatches_and_blank_regions() {
+    let mut sim = sim();
+    sim.program_page(0x1200, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(sim.verify(0x1200, 4, Some(&[1, 2, 3, 4])), Ok(()));
+    assert_eq!(
+        sim.verify(0x1200, 4, Some(&[1, 2, 3, 5])),
+        Err(ErrorCode::VerifyMismatch.into())
+    );
+    assert_eq!(
+        sim.verify(0x1200, 4, Some(&[1, 2, 3])),
+        Err(ErrorCode::VerifyMismatch.into())
+    );
+    assert_eq!(sim.verify(0x1204, 0xFC, None), Ok(()));
+    assert_eq!(
+        sim.verify(0x1200, 0x100, None),
+        Err(ErrorCode::VerifyMismatch.into())
+    );
+}
+
+#[test]
+fn empty_pattern_defines_erased_contents() {
+    let mut sim = sim().with_empty_pattern(&[0xA5, 0x5A]);
+    assert_eq!(sim.memory()[..4], [0xA5, 0x5A, 0xA5, 0x5A]);
+    assert_eq!(sim.is_sector_erased(0x1000), Ok(true));
+    assert_eq!(sim.verify(0x1001, 3, None), Ok(()));
+
+    sim.program_page(0x1002, &[0x00, 0x00]).unwrap();
+    assert_eq!(sim.is_sector_erased(0x1000), Ok(false));
+    assert_eq!(
+        sim.program_page(0x1002, &[0x00]),
+        Err(ErrorCode::ProgramFailed.into())
+    );
+    sim.erase_sector(0x1000).unwrap();
+    assert_eq!(sim.memory()[..4], [0xA5, 0x5A, 0xA5, 0x5A]);
+    assert_eq!(sim.empty_value(), 0xFF);
+}
+
+#[test]
+fn is_sector_erased_checks_the_sector_of_the_address() {
+    let mut sim = sim();
+    assert_eq!(sim.is_sector_erased(0x1300), Ok(true));
+    sim.program_page(0x13FF, &[0x7F]).unwrap();
+    assert_eq!(sim.is_sector_erased(0x1200), Ok(false));
+    assert_eq!(sim.is_sector_erased(0x10FF), Ok(true));
+    sim.erase_sector(0x1300).unwrap();
+    assert_eq!(sim.is_sector_erased(0x13FF), Ok(true));
+    assert_eq!(
+        sim.is_sector_erased(0x1400),
+        Err(ErrorCode::AddressOutOfRange.into())
+    );
+}
@@ -0,0 +1,95 @@
+use flashops::{error_codes, flash_algorithm, FlashOps, Operation, ERASE, PROGRAM, VERIFY};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Host;
+
+flash_algorithm!(Algo<Host>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+fn pages(len: usize) -> Vec<u8> {
+    (0..len).map(|i| i as u8).collect()
+}
+
+#[test]
+fn initialize_replaces_a_running_instance() {
+    let _serial = fixture::start::<Algo<Host>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(current_operation(), Some(Operation::Program));
+    assert_eq!(
+        fixture::device().calls,
+        [
+            Call::Create(Operation::Erase),
+            Call::Create(Operation::Program)
+        ]
+    );
+    assert_eq!(__deinit(), 0);
+    assert_eq!(__deinit(), error_codes::NOT_INITIALIZED.get());
+}
+
+#[test]
+fn initialize_rejects_unknown_operations() {
+    let _serial = fixture::start::<Algo<Host>>(__deinit);
+    assert_eq!(
+        __init(0x0800_0000, 0, 7),
+        error_codes::INVALID_OPERATION.get()
+    );
+    assert_eq!(current_operation(), None);
+    assert!(fixture::device().calls.is_empty());
+}
+
+#[test]
+fn erase_chip_erases_every_sector() {
+    let _serial = fixture::start::<Algo<Host>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    for sector in (0x0800_0000..0x0800_4000).step_by(0x1000) {
+        assert_eq!(__program_page(sector, &pages(0x100)), 0);
+    }
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    fixture::device().calls.clear();
+
+    assert_eq!(__erase_chip(), 0);
+    let device = fixture::device();
+    assert!(device.sim.memory().iter().all(|&byte| byte == 0xFF));
+    assert_eq!(
+        device.calls,
+        [0x0800_0000, 0x0800_1000, 0x0800_2000, 0x0800_3000].map(Call::EraseSector)
+    );
+}
+
+#[test]
+fn program_pages_then_verify() {
+    let _serial = fixture::start::<Algo<Host>>(__deinit);
+    let data = pages(0x300);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_pages(0x0800_0100, &data), 0);
+    assert_eq!(fixture::device().sim.memory()[0x100..0x400], data[..]);
+
+    assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    assert_eq!(__verify(0x0800_0100, &data), 0);
+    let mut changed = data.clone();
+    changed[0x180] ^= 1;
+    assert_eq!(
+        __verify(0x0800_0100, &changed),
+        error_codes::VERIFY_MISMATCH.get()
+    );
+}
+
+#[test]
+fn blank_check_reports_programmed_bytes() {
+    let _serial = fixture::start::<Algo<Host>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__blank_check(0x0800_0000, 0x1000), 0);
+    assert_eq!(__program_page(0x0800_0200, &[0; 4]), 0);
+    assert_eq!(
+        __blank_check(0x0800_0000, 0x1000),
+        error_codes::NOT_BLANK.get()
+    );
+    assert_eq!(__blank_check(0x0800_1000, 0x1000), 0);
+}
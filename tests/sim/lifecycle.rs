@@ -0,0 +1,64 @@
+use flashops::error_codes::NOT_INITIALIZED;
+use flashops::{flash_algorithm, FlashOps, ERASE, PROGRAM};
+
+use crate::fixture::{self, Algo, CREATE_FAILED};
+
+pub struct Lifecycle;
+
+flash_algorithm!(Algo<Lifecycle>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+#[test]
+fn failed_creates_never_drop_an_instance() {
+    let _serial = fixture::start::<Algo<Lifecycle>>(__deinit);
+    for _ in 0..3 {
+        fixture::device().failing_creates = 1;
+        assert_eq!(__init(0x0800_0000, 0, ERASE), CREATE_FAILED.get());
+        assert_eq!(fixture::device().drops, 0);
+    }
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(fixture::device().drops, 0);
+    assert_eq!(__deinit(), 0);
+    assert_eq!(fixture::device().drops, 1);
+}
+
+#[test]
+fn a_failed_reinit_drops_the_previous_instance_once() {
+    let _serial = fixture::start::<Algo<Lifecycle>>(__deinit);
+    for round in 1..=3 {
+        assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+        fixture::device().failing_creates = 1;
+        assert_eq!(__init(0x0800_0000, 0, ERASE), CREATE_FAILED.get());
+        assert_eq!(fixture::device().drops, round);
+        assert_eq!(__deinit(), NOT_INITIALIZED.get());
+        assert_eq!(fixture::device().drops, round);
+    }
+}
+
+#[test]
+fn entry_points_after_a_failed_init_report_not_initialized() {
+    let _serial = fixture::start::<Algo<Lifecycle>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    fixture::device().failing_creates = 1;
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), CREATE_FAILED.get());
+    fixture::device().calls.clear();
+
+    let not_initialized = NOT_INITIALIZED.get();
+    let mut buffer = [0u8; 0x100];
+    assert_eq!(__erase_sector(0x0800_0000), not_initialized);
+    assert_eq!(__program_page(0x0800_0000, &buffer), not_initialized);
+    assert_eq!(__program_pages(0x0800_0000, &buffer), not_initialized);
+    assert_eq!(__erase_chip(), not_initialized);
+    assert_eq!(__verify(0x0800_0000, &buffer), not_initialized);
+    assert_eq!(__read(0x0800_0000, &mut buffer), not_initialized);
+    assert_eq!(__blank_check(0x0800_0000, 0x100), not_initialized);
+    assert_eq!(__finalize(), not_initialized);
+    assert_eq!(__deinit(), not_initialized);
+    assert_eq!(current_operation(), None);
+    assert!(fixture::device().calls.is_empty());
+}
@@ -0,0 +1,29 @@
+//! Host tests driving algorithms generated by `flash_algorithm!` against a `FlashSim`.
+
+mod blank_verify;
+mod buffer_alignment;
+mod checksum;
+mod chunked;
+mod counted;
+mod data_region;
+mod device_id;
+mod erase_if_needed;
+mod erase_progress;
+mod erase_step;
+mod erased;
+mod fixture;
+mod flash_sim;
+mod host;
+mod lifecycle;
+mod mmio;
+mod padding;
+mod program_verify;
+mod read;
+mod regions;
+mod relocatable;
+mod strict_program;
+mod teardown;
+mod uniform;
+mod unlock;
+mod write_config;
+mod zero_length;
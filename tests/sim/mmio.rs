@@ -0,0 +1,270 @@
+//! `mmio_flash!` driving a modelled STM32F1-style controller whose flash is the suite's
+//! `FlashSim`.
+
+use std::sync::Mutex;
+
+use flashops::error_codes::{ERASE_FAILED, INVALID_ARGUMENT, PROGRAM_FAILED, TIMEOUT};
+use flashops::{
+    flash_algorithm, mmio_flash, Bus, Error, FlashOps, MmioController, Word, ERASE, PROGRAM,
+};
+
+use crate::fixture;
+
+const KEYR: u32 = 0x4002_2004;
+const SR: u32 = 0x4002_200C;
+const CR: u32 = 0x4002_2010;
+const AR: u32 = 0x4002_2014;
+const KEYS: [u32; 2] = [0x4567_0123, 0xCDEF_89AB];
+
+const PG: u32 = 1 << 0;
+const PER: u32 = 1 << 1;
+const STRT: u32 = 1 << 6;
+const LOCK: u32 = 1 << 7;
+const BSY: u32 = 1 << 0;
+const PGERR: u32 = 1 << 2;
+
+const CONTROLLER: MmioController = MmioController {
+    unlock: &[(KEYR, KEYS[0]), (KEYR, KEYS[1])],
+    control: CR,
+    program: PG,
+    erase: PER,
+    start: STRT,
+    lock: LOCK,
+    erase_address: AR,
+    status: SR,
+    busy: BSY,
+    errors: PGERR,
+    word: Word::U16,
+    empty: 0xFF,
+    max_polls: 8,
+};
+
+/// Controller state behind [`ModelBus`], shared because `create` builds the bus itself.
+struct Model {
+    keys: usize,
+    control: u32,
+    erase_address: u32,
+    status: u32,
+    /// Status reads that still report busy after each operation starts.
+    busy_polls: u32,
+    remaining: u32,
+}
+
+static MODEL: Mutex<Model> = Mutex::new(Model {
+    keys: 0,
+    control: LOCK,
+    erase_address: 0,
+    status: 0,
+    busy_polls: 0,
+    remaining: 0,
+});
+
+fn model() -> std::sync::MutexGuard<'static, Model> {
+    MODEL
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn reset(busy_polls: u32) {
+    let mut model = model();
+    model.keys = 0;
+    model.control = LOCK;
+    model.status = 0;
+    model.busy_polls = busy_polls;
+    model.remaining = 0;
+}
+
+impl Model {
+    fn unlocked(&self) -> bool {
+        self.keys == KEYS.len()
+    }
+
+    fn start(&mut self, result: Result<(), Error>) {
+        self.status = if result.is_ok() { 0 } else { PGERR };
+        self.remaining = self.busy_polls;
+    }
+}
+
+#[derive(Default)]
+pub struct ModelBus;
+
+impl Bus for ModelBus {
+    unsafe fn read32(&mut self, address: u32) -> u32 {
+        assert_eq!(address, SR, "read of an unmodelled register");
+        let mut model = model();
+        if model.remaining > 0 {
+            model.remaining -= 1;
+            model.status | BSY
+        } else {
+            model.status
+        }
+    }
+
+    unsafe fn write(&mut self, address: u32, value: u32, width: Word) {
+        let mut model = model();
+        match address {
+            KEYR => {
+                let expected = KEYS.get(model.keys).copied();
+                model.keys = if expected == Some(value) {
+                    model.keys + 1
+                } else {
+                    0
+                };
+            }
+            AR => model.erase_address = value,
+            CR => {
+                if value & LOCK != 0 {
+                    model.keys = 0;
+                }
+                model.control = value;
+                if value == PER | STRT {
+                    let result = if model.unlocked() {
+                        fixture::device().sim.erase_sector(model.erase_address)
+                    } else {
+                        Err(ERASE_FAILED)
+                    };
+                    model.start(result);
+                }
+            }
+            _ => {
+                let bytes = value.to_le_bytes();
+                let result = if model.unlocked() && model.control == PG {
+                    fixture::device()
+                        .sim
+                        .program_page(address, &bytes[..width.bytes()])
+                } else {
+                    Err(PROGRAM_FAILED)
+                };
+                model.start(result);
+            }
+        }
+    }
+}
+
+mmio_flash!(unsafe pub struct Stm32f1, CONTROLLER, {
+    fn read(&mut self, address: u32, data: &mut [u8]) -> Result<(), Error> {
+        fixture::device().sim.read(address, data)
+    }
+
+    fn blank_check(&mut self, address: u32, size: u32) -> Result<(), Error> {
+        fixture::device().sim.verify(address, size, None)
+    }
+
+    fn checksum(&mut self, address: u32, size: u32) -> Result<u32, Error> {
+        fixture::device().sim.checksum(address, size)
+    }
+
+    fn read_device_id(&mut self) -> Result<u32, Error> {
+        fixture::device().sim.read_device_id()
+    }
+
+    fn write_config(&mut self, _reg: u32, _value: u32) -> Result<(), Error> {
+        Err(INVALID_ARGUMENT)
+    }
+});
+
+flash_algorithm!(Stm32f1<ModelBus>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x1000,
+    page_size: 0x400,
+    empty_value: 0xFF,
+    sectors: [{size: 0x400, address: 0x0}]
+});
+
+#[test]
+fn programs_words_after_unlocking_and_locks_on_deinit() {
+    let _serial = fixture::start::<Stm32f1<ModelBus>>(__deinit);
+    reset(3);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert!(!model().unlocked());
+    assert_eq!(__program_page(0x0800_0400, &[0x11, 0x22, 0x33]), 0);
+    assert!(model().unlocked());
+    assert_eq!(
+        fixture::device().sim.memory()[0x400..0x405],
+        [0x11, 0x22, 0x33, 0xFF, 0xFF]
+    );
+    assert_eq!(__deinit(), 0);
+    assert!(!model().unlocked());
+    assert_eq!(model().control, LOCK);
+}
+
+#[test]
+fn erases_the_addressed_sector() {
+    let _serial = fixture::start::<Stm32f1<ModelBus>>(__deinit);
+    reset(2);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_page(0x0800_0800, &[0; 4]), 0);
+    assert_eq!(__deinit(), 0);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__erase_sector(0x0800_0800), 0);
+    assert!(fixture::device()
+        .sim
+        .memory()
+        .iter()
+        .all(|&byte| byte == 0xFF));
+    assert_eq!(model().control, 0);
+}
+
+#[test]
+fn reports_controller_errors() {
+    let _serial = fixture::start::<Stm32f1<ModelBus>>(__deinit);
+    reset(0);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_page(0x0800_0000, &[0; 2]), 0);
+    assert_eq!(__program_page(0x0800_0000, &[0; 2]), PROGRAM_FAILED.get());
+    assert_eq!(__deinit(), 0);
+}
+
+#[test]
+fn a_controller_stuck_busy_times_out() {
+    let _serial = fixture::start::<Stm32f1<ModelBus>>(__deinit);
+    reset(CONTROLLER.max_polls);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__erase_sector(0x0800_0000), TIMEOUT.get());
+    reset(CONTROLLER.max_polls - 1);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__erase_sector(0x0800_0000), 0);
+}
+
+mod big_endian {
+    use flashops::{flash_algorithm, Bus, FlashOps, Word, WordOrder, PROGRAM};
+
+    use super::{reset, ModelBus, Stm32f1};
+    use crate::fixture;
+
+    /// The model's bus, for a second geometry whose words are assembled big-endian.
+    #[derive(Default)]
+    pub struct SwappedBus(ModelBus);
+
+    impl Bus for SwappedBus {
+        unsafe fn read32(&mut self, address: u32) -> u32 {
+            self.0.read32(address)
+        }
+
+        unsafe fn write(&mut self, address: u32, value: u32, width: Word) {
+            self.0.write(address, value, width)
+        }
+    }
+
+    flash_algorithm!(Stm32f1<SwappedBus>, {
+        flash_address: 0x0800_0000,
+        flash_size: 0x1000,
+        page_size: 0x400,
+        empty_value: 0xFF,
+        sectors: [{size: 0x400, address: 0x0}],
+        word_order: WordOrder::BigEndian
+    });
+
+    #[test]
+    fn halfwords_take_their_first_byte_as_the_high_one() {
+        let _serial = fixture::start::<Stm32f1<SwappedBus>>(__deinit);
+        reset(0);
+        assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+        assert_eq!(__program_page(0x0800_0000, &[0x11, 0x22, 0x33]), 0);
+        // The bus stores each halfword little-endian, so the bytes land swapped in pairs.
+        assert_eq!(
+            fixture::device().sim.memory()[..5],
+            [0x22, 0x11, 0xFF, 0x33, 0xFF]
+        );
+    }
+}
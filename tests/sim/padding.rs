@@ -0,0 +1,92 @@
+use flashops::{flash_algorithm, program_padded, FlashGeometry, FlashOps, Operation};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Padded;
+
+flash_algorithm!(Algo<Padded>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}],
+    empty_pattern: [0xFF, 0x00]
+});
+
+/// A page at the very top of the address space, whose end is `u32::MAX + 1`.
+pub struct Top;
+
+impl FlashGeometry for Algo<Top> {
+    const FLASH_ADDRESS: u32 = 0xFFFF_FF00;
+    const FLASH_SIZE: u32 = 0x100;
+    const PAGE_SIZE: u32 = 0x100;
+    const EMPTY_VALUE: u8 = 0xFF;
+    const PROGRAM_TIMEOUT: u32 = 100;
+    const ERASE_TIMEOUT: u32 = 100;
+    const SECTORS: &'static [(u32, u32)] = &[(0x100, 0x0)];
+}
+
+fn calls() -> Vec<Call> {
+    fixture::device().calls[1..].to_vec()
+}
+
+#[test]
+fn short_chunk_is_padded_with_the_empty_pattern() {
+    let _serial = fixture::start::<Algo<Padded>>(__deinit);
+    let mut algo = Algo::<Padded>::create(0x0800_0000, 0, Operation::Program).unwrap();
+    program_padded::<_, 0x100>(&mut algo, 0x0800_0180, &[1, 2, 3]).unwrap();
+    assert_eq!(calls(), [Call::ProgramPage(0x0800_0180, 0x80)]);
+    let device = fixture::device();
+    let memory = &device.sim.memory()[0x180..0x200];
+    assert_eq!(memory[..5], [1, 2, 3, 0x00, 0xFF]);
+    assert!(memory.chunks(2).skip(2).all(|pair| pair == [0xFF, 0x00]));
+}
+
+#[test]
+fn whole_and_multi_page_data_is_passed_through() {
+    let _serial = fixture::start::<Algo<Padded>>(__deinit);
+    let mut algo = Algo::<Padded>::create(0x0800_0000, 0, Operation::Program).unwrap();
+    program_padded::<_, 0x100>(&mut algo, 0x0800_0100, &[7; 0x100]).unwrap();
+    program_padded::<_, 0x100>(&mut algo, 0x0800_0280, &[8; 0x80]).unwrap();
+    assert_eq!(
+        calls(),
+        [
+            Call::ProgramPage(0x0800_0100, 0x100),
+            Call::ProgramPage(0x0800_0280, 0x80)
+        ]
+    );
+}
+
+#[test]
+fn padding_reaches_the_top_of_the_address_space() {
+    let _serial = fixture::start::<Algo<Top>>(|| 0);
+    let mut algo = Algo::<Top>::create(0xFFFF_FF00, 0, Operation::Program).unwrap();
+    program_padded::<_, 0x100>(&mut algo, 0xFFFF_FFF0, &[0x42; 4]).unwrap();
+    assert_eq!(calls(), [Call::ProgramPage(0xFFFF_FFF0, 0x10)]);
+    let device = fixture::device();
+    let memory = &device.sim.memory()[0xF0..];
+    assert_eq!(memory[..4], [0x42; 4]);
+    assert!(memory[4..].iter().all(|&byte| byte == 0xFF));
+}
+
+#[test]
+fn erased_checks_reach_the_top_of_the_address_space() {
+    let _serial = fixture::start::<Algo<Top>>(|| 0);
+    let mut algo = Algo::<Top>::create(0xFFFF_FF00, 0, Operation::Verify).unwrap();
+    assert_eq!(algo.verify(0xFFFF_FF00, 0x100, None), Ok(()));
+    assert_eq!(algo.is_sector_erased(0xFFFF_FFFF), Ok(true));
+    fixture::device()
+        .sim
+        .program_page(0xFFFF_FFFF, &[0])
+        .unwrap();
+    assert!(algo.verify(0xFFFF_FF00, 0x100, None).is_err());
+    assert_eq!(algo.is_sector_erased(0xFFFF_FF00), Ok(false));
+}
+
+#[test]
+fn erase_range_stops_after_the_sector_at_the_top_of_the_address_space() {
+    let _serial = fixture::start::<Algo<Top>>(|| 0);
+    let mut algo = Algo::<Top>::create(0xFFFF_FF00, 0, Operation::Erase).unwrap();
+    assert_eq!(algo.erase_range(0xFFFF_FF00, 0xFF), Ok(()));
+    assert_eq!(calls(), [Call::EraseSector(0xFFFF_FF00)]);
+}
@@ -0,0 +1,47 @@
+use flashops::error_codes::VERIFY_MISMATCH;
+use flashops::{flash_algorithm, FlashOps, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct ProgramVerify;
+
+flash_algorithm!(Algo<ProgramVerify>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+fn program_and_verify_page(address: u32, data: &[u8]) -> u32 {
+    unsafe { program_and_verify(address, data.len() as u32, data.as_ptr()) }
+}
+
+#[test]
+fn a_page_that_reads_back_intact_passes() {
+    let _serial = fixture::start::<Algo<ProgramVerify>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    let data: Vec<u8> = (0..0x80).collect();
+    assert_eq!(program_and_verify_page(0x0800_0100, &data), 0);
+    assert_eq!(
+        fixture::device().calls[1..],
+        [
+            Call::ProgramPage(0x0800_0100, 0x80),
+            Call::Read(0x0800_0100, 0x40),
+            Call::Read(0x0800_0140, 0x40),
+        ]
+    );
+}
+
+#[test]
+fn a_byte_that_reads_back_wrong_is_a_mismatch() {
+    let _serial = fixture::start::<Algo<ProgramVerify>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    fixture::device().weak_byte = Some(0x0800_0150);
+    let data: Vec<u8> = (0..0x80).collect();
+    assert_eq!(
+        program_and_verify_page(0x0800_0100, &data),
+        VERIFY_MISMATCH.get()
+    );
+    assert_eq!(fixture::device().sim.memory()[0x150], !0x50);
+}
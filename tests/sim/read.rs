@@ -0,0 +1,51 @@
+use flashops::{error_codes, flash_algorithm, FlashOps, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Reads;
+
+flash_algorithm!(Algo<Reads>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+#[test]
+fn read_returns_programmed_bytes() {
+    let _serial = fixture::start::<Algo<Reads>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    let page: Vec<u8> = (0..=255).collect();
+    assert_eq!(__program_page(0x0800_0100, &page), 0);
+
+    let mut back = [0u8; 0x100];
+    assert_eq!(__read(0x0800_0100, &mut back), 0);
+    assert_eq!(back[..], page[..]);
+    assert_eq!(
+        fixture::device().calls.last(),
+        Some(&Call::Read(0x0800_0100, 0x100))
+    );
+    assert_eq!(__deinit(), 0);
+}
+
+#[test]
+fn read_before_initialize_fails() {
+    let _serial = fixture::start::<Algo<Reads>>(__deinit);
+    let mut back = [0u8; 4];
+    let result = unsafe { flash_read(0x0800_0000, back.len() as u32, back.as_mut_ptr()) };
+    assert_eq!(result, error_codes::NOT_INITIALIZED.get());
+    assert_eq!(
+        __read(0x0800_0000, &mut back),
+        error_codes::NOT_INITIALIZED.get()
+    );
+    assert!(fixture::device().calls.is_empty());
+}
+
+/// The entry point must not take over the C library's `read`, which the test harness and
+/// `std::fs` rely on.
+#[test]
+fn libc_read_still_reads_files() {
+    let manifest = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap();
+    assert!(manifest.trim_ascii_start().starts_with(b"[package]"));
+}
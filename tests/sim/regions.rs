@@ -0,0 +1,120 @@
+use flashops::{
+    error_codes, flash_algorithm, sector_for_address, FlashGeometry, FlashOps, Operation,
+    StepResult, ERASE, PROGRAM,
+};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Banks;
+
+flash_algorithm!(Algo<Banks>, {
+    regions: [
+        {address: 0x0800_0000, size: 0x2000, sectors: [{size: 0x800, address: 0x0}]},
+        {address: 0x0810_0000, size: 0x800, sectors: [{size: 0x800, address: 0x0}]}
+    ],
+    page_size: 0x100,
+    empty_value: 0xFF
+});
+
+const BASES: [u32; 5] = [
+    0x0800_0000,
+    0x0800_0800,
+    0x0800_1000,
+    0x0800_1800,
+    0x0810_0000,
+];
+
+#[test]
+fn geometry_excludes_the_gap() {
+    type A = Algo<Banks>;
+    assert_eq!(A::REGIONS, [(0x0800_0000, 0x2000), (0x0810_0000, 0x800)]);
+    assert!(A::contains(0x0800_1F00, 0x100));
+    assert!(A::contains(0x0810_0000, 0x800));
+    assert!(!A::contains(0x0800_1F00, 0x200));
+    assert!(!A::contains(0x0805_0000, 1));
+    assert_eq!(
+        sector_for_address::<A>(0x0800_1FFF),
+        Some((0x800, 0x0800_1800))
+    );
+    assert_eq!(sector_for_address::<A>(0x0800_2000), None);
+    assert_eq!(sector_for_address::<A>(0x0805_0000), None);
+    assert_eq!(
+        sector_for_address::<A>(0x0810_0400),
+        Some((0x800, 0x0810_0000))
+    );
+}
+
+#[test]
+fn erase_chip_only_erases_real_sectors() {
+    let _serial = fixture::start::<Algo<Banks>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    fixture::device().calls.clear();
+    assert_eq!(__erase_chip(), 0);
+    assert_eq!(fixture::device().calls, BASES.map(Call::EraseSector));
+}
+
+#[test]
+fn erase_chip_step_walks_both_banks() {
+    let _serial = fixture::start::<Algo<Banks>>(__deinit);
+    let mut algo = Algo::<Banks>::create(0x0800_0000, 0, Operation::Erase).unwrap();
+    for step in 0..BASES.len() as u32 - 1 {
+        assert_eq!(algo.erase_chip_step(step), Ok(StepResult::InProgress));
+    }
+    assert_eq!(
+        algo.erase_chip_step(BASES.len() as u32 - 1),
+        Ok(StepResult::Done)
+    );
+    let calls = &fixture::device().calls[1..];
+    assert_eq!(calls, BASES.map(Call::EraseSector));
+}
+
+#[test]
+fn bounds_check_rejects_the_gap() {
+    let _serial = fixture::start::<Algo<Banks>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(
+        __erase_sector(0x0805_0000),
+        error_codes::ADDRESS_OUT_OF_RANGE.get()
+    );
+    assert_eq!(
+        __erase_sector(0x0800_2000),
+        error_codes::ADDRESS_OUT_OF_RANGE.get()
+    );
+    assert_eq!(__erase_sector(0x0810_0000), 0);
+
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(
+        __program_page(0x0805_0000, &[0; 0x100]),
+        error_codes::ADDRESS_OUT_OF_RANGE.get()
+    );
+    assert_eq!(__program_page(0x0810_0700, &[0; 0x100]), 0);
+    assert_eq!(
+        fixture::device().calls[1..],
+        [
+            Call::EraseSector(0x0810_0000),
+            Call::Create(Operation::Program),
+            Call::ProgramPage(0x0810_0700, 0x100),
+        ]
+    );
+}
+
+#[test]
+fn erase_chip_clears_both_banks_and_leaves_the_gap() {
+    let _serial = fixture::start::<Algo<Banks>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    for base in BASES {
+        assert_eq!(__program_page(base + 0x700, &[0x5A; 0x100]), 0);
+    }
+    fixture::device()
+        .sim
+        .program_page(0x0805_0000, &[0x12])
+        .unwrap();
+
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__erase_chip(), 0);
+    let device = fixture::device();
+    let memory = device.sim.memory();
+    assert!(memory[..0x2000].iter().all(|&byte| byte == 0xFF));
+    assert!(memory[0x10_0000..].iter().all(|&byte| byte == 0xFF));
+    assert_eq!(memory[0x5_0000], 0x12);
+}
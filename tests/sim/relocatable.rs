@@ -0,0 +1,77 @@
+use flashops::error_codes::ADDRESS_OUT_OF_RANGE;
+use flashops::{descriptor_crc, flash_algorithm_for_region, FlashGeometry, FlashOps, ERASE};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Relocatable;
+
+flash_algorithm_for_region!(Algo<Relocatable>, {
+    flash_address: 0x9000_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}, {size: 0x2000, address: 0x2000}]
+});
+
+#[test]
+fn descriptors_hold_offsets_from_the_load_base() {
+    assert_eq!(LOAD_BASE, 0x9000_0000);
+    assert_eq!(FlashLoadBase, 0x9000_0000);
+    assert_eq!(FlashDeviceInfo.dev_addr, 0);
+    assert_eq!(FlashDeviceInfo.device_size, 0x4000);
+    assert_eq!(
+        FlashDeviceInfo.flash_sectors,
+        [
+            Sector {
+                size: 0x1000,
+                address: 0x0
+            },
+            Sector {
+                size: 0x2000,
+                address: 0x2000
+            },
+        ]
+    );
+    assert_eq!(FlashAlgorithmInfo.flash_address, 0);
+    assert_eq!(
+        DESCRIPTOR_CRC,
+        descriptor_crc(0, 0x4000, 0x100, <Algo<Relocatable>>::SECTORS)
+    );
+}
+
+#[test]
+fn entry_points_still_take_absolute_addresses() {
+    let _serial = fixture::start::<Algo<Relocatable>>(__deinit);
+    assert_eq!(<Algo<Relocatable>>::FLASH_ADDRESS, 0x9000_0000);
+    assert_eq!(__init(0x9000_0000, 0, ERASE), 0);
+    assert_eq!(__erase_sector(0x9000_2000), 0);
+    assert_eq!(__erase_sector(0x2000), ADDRESS_OUT_OF_RANGE.get());
+    assert_eq!(
+        fixture::device().calls[1..],
+        [Call::EraseSector(0x9000_2000)]
+    );
+}
+
+mod device_address {
+    use flashops::{flash_algorithm_for_region, FlashOps};
+
+    use crate::fixture::Algo;
+
+    pub struct Aliased;
+
+    flash_algorithm_for_region!(Algo<Aliased>, {
+        flash_address: 0x9000_0000,
+        flash_size: 0x4000,
+        page_size: 0x100,
+        empty_value: 0xFF,
+        sectors: [{size: 0x1000, address: 0x0}],
+        device_address: 0x7000_0000
+    });
+
+    #[test]
+    fn the_device_address_becomes_the_load_base() {
+        assert_eq!(FlashLoadBase, 0x7000_0000);
+        assert_eq!(FlashDeviceInfo.dev_addr, 0);
+        assert_eq!(FlashAlgorithmInfo.flash_address, 0x2000_0000);
+    }
+}
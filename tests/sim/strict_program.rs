@@ -0,0 +1,79 @@
+use flashops::error_codes::UNALIGNED_PROGRAM;
+use flashops::{flash_algorithm, FlashOps, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Strict;
+
+flash_algorithm!(Algo<Strict>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}],
+    data_region: {
+        address: 0x0808_0000,
+        size: 0x1000,
+        page_size: 0x40
+    }
+});
+
+fn calls() -> Vec<Call> {
+    fixture::device().calls[1..].to_vec()
+}
+
+#[test]
+fn page_aligned_programs_are_accepted_whatever_their_length() {
+    let _serial = fixture::start::<Algo<Strict>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_page(0x0800_0000, &[0; 0x100]), 0);
+    assert_eq!(__program_page(0x0800_0100, &[0; 0x10]), 0);
+    assert_eq!(__program_page(0x0808_0040, &[0; 0x10]), 0);
+    assert_eq!(
+        calls(),
+        [
+            Call::ProgramPage(0x0800_0000, 0x100),
+            Call::ProgramPage(0x0800_0100, 0x10),
+            Call::ProgramData(0x0808_0040, 0x10),
+        ]
+    );
+}
+
+#[test]
+fn programs_off_a_page_boundary_never_reach_the_algorithm() {
+    let _serial = fixture::start::<Algo<Strict>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    let data = [0u8; 0x10];
+    assert_eq!(__program_page(0x0800_0010, &data), UNALIGNED_PROGRAM.get());
+    assert_eq!(
+        __program_page(0x0800_00FF, &data[..1]),
+        UNALIGNED_PROGRAM.get()
+    );
+    let mut count = 0;
+    assert_eq!(
+        unsafe { program_page_counted(0x0800_0010, 0x10, data.as_ptr(), &mut count) },
+        UNALIGNED_PROGRAM.get()
+    );
+    assert_eq!(
+        unsafe { program_and_verify(0x0800_0010, 0x10, data.as_ptr()) },
+        UNALIGNED_PROGRAM.get()
+    );
+    assert!(calls().is_empty());
+    assert!(fixture::device()
+        .sim
+        .memory()
+        .iter()
+        .all(|&byte| byte == 0xFF));
+}
+
+#[test]
+fn data_region_programs_are_aligned_to_its_own_pages() {
+    let _serial = fixture::start::<Algo<Strict>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    // Inside a 0x40-byte data page rather than at its start.
+    assert_eq!(
+        __program_page(0x0808_0020, &[0; 0x10]),
+        UNALIGNED_PROGRAM.get()
+    );
+    assert!(calls().is_empty());
+}
@@ -0,0 +1,63 @@
+use flashops::error_codes::NOT_INITIALIZED;
+use flashops::{flash_algorithm, Error, FlashOps, Operation, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Teardown;
+
+flash_algorithm!(Algo<Teardown>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+const UNINIT_FAILED: Error = Error::new(0x21).unwrap();
+const DEINIT_FAILED: Error = Error::new(0x22).unwrap();
+
+/// Initializes for programming and makes `failing` fail with `error`.
+fn begin(failing: Call, error: Error) {
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    let mut device = fixture::device();
+    device.trace_hooks = true;
+    device.failing_hook = Some((failing, error));
+}
+
+#[test]
+fn a_deinit_error_is_returned_and_the_instance_dropped() {
+    let _serial = fixture::start::<Algo<Teardown>>(__deinit);
+    begin(Call::Deinit, DEINIT_FAILED);
+    assert_eq!(__deinit(), DEINIT_FAILED.get());
+    let device = fixture::device();
+    assert_eq!(device.drops, 1);
+    assert_eq!(
+        device.calls[1..],
+        [Call::Uninit(Operation::Program), Call::Deinit]
+    );
+}
+
+#[test]
+fn deinit_runs_after_a_failed_uninit_whose_error_wins() {
+    let _serial = fixture::start::<Algo<Teardown>>(__deinit);
+    begin(Call::Uninit(Operation::Program), UNINIT_FAILED);
+    assert_eq!(__deinit(), UNINIT_FAILED.get());
+    let device = fixture::device();
+    assert_eq!(device.drops, 1);
+    assert_eq!(
+        device.calls[1..],
+        [Call::Uninit(Operation::Program), Call::Deinit]
+    );
+}
+
+#[test]
+fn the_algorithm_is_uninitialized_after_a_failed_deinit() {
+    let _serial = fixture::start::<Algo<Teardown>>(__deinit);
+    begin(Call::Deinit, DEINIT_FAILED);
+    assert_eq!(__deinit(), DEINIT_FAILED.get());
+    assert_eq!(__deinit(), NOT_INITIALIZED.get());
+    assert_eq!(__program_page(0x0800_0000, &[0; 4]), NOT_INITIALIZED.get());
+    fixture::device().failing_hook = None;
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__deinit(), 0);
+}
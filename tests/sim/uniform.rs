@@ -0,0 +1,41 @@
+use flashops::{flash_algorithm, sector_for_address, FlashGeometry, FlashOps, ERASE};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Uniform;
+
+flash_algorithm!(Algo<Uniform>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: uniform {size: 0x1000, count: 3, start: 0x0800_1000}
+});
+
+#[test]
+fn start_is_an_absolute_address() {
+    type A = Algo<Uniform>;
+    assert_eq!(A::SECTORS, [(0x1000, 0x1000)]);
+    assert_eq!(sector_for_address::<A>(0x0800_0FFF), None);
+    assert_eq!(
+        sector_for_address::<A>(0x0800_1000),
+        Some((0x1000, 0x0800_1000))
+    );
+    assert_eq!(
+        sector_for_address::<A>(0x0800_3FFF),
+        Some((0x1000, 0x0800_3000))
+    );
+    assert_eq!(sector_for_address::<A>(0x0800_4000), None);
+}
+
+#[test]
+fn erase_chip_starts_at_the_first_sector() {
+    let _serial = fixture::start::<Algo<Uniform>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    fixture::device().calls.clear();
+    assert_eq!(__erase_chip(), 0);
+    assert_eq!(
+        fixture::device().calls,
+        [0x0800_1000, 0x0800_2000, 0x0800_3000].map(Call::EraseSector)
+    );
+}
@@ -0,0 +1,122 @@
+use flashops::{flash_algorithm, Error, FlashOps, Operation, ERASE, PROGRAM, VERIFY};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Unlock;
+
+flash_algorithm!(Algo<Unlock>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+const UNLOCK_FAILED: Error = Error::new(0x31).unwrap();
+const LOCK_FAILED: Error = Error::new(0x32).unwrap();
+
+/// Starts a test that records the lifecycle hooks.
+fn traced() -> std::sync::MutexGuard<'static, ()> {
+    let serial = fixture::start::<Algo<Unlock>>(__deinit);
+    fixture::device().trace_hooks = true;
+    serial
+}
+
+#[test]
+fn unlock_precedes_the_first_request_and_lock_follows_the_last() {
+    let _serial = traced();
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__erase_sector(0x0800_0000), 0);
+    assert_eq!(__program_page(0x0800_0000, &[0; 0x100]), 0);
+    assert_eq!(__erase_sector(0x0800_1000), 0);
+    assert_eq!(__deinit(), 0);
+    assert_eq!(
+        fixture::device().calls,
+        [
+            Call::Create(Operation::Erase),
+            Call::Unlock,
+            Call::EraseSector(0x0800_0000),
+            Call::ProgramPage(0x0800_0000, 0x100),
+            Call::EraseSector(0x0800_1000),
+            Call::Lock,
+            Call::Uninit(Operation::Erase),
+            Call::Deinit,
+        ]
+    );
+}
+
+#[test]
+fn a_session_without_requests_never_unlocks() {
+    let _serial = traced();
+    assert_eq!(__init(0x0800_0000, 0, VERIFY), 0);
+    assert_eq!(__verify(0x0800_0000, &[0xFF; 0x10]), 0);
+    assert_eq!(__deinit(), 0);
+    let calls = fixture::device().calls.clone();
+    assert!(!calls.contains(&Call::Unlock), "{calls:?}");
+    assert!(!calls.contains(&Call::Lock), "{calls:?}");
+}
+
+#[test]
+fn a_failed_unlock_is_retried_by_the_next_request() {
+    let _serial = traced();
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    fixture::device().failing_hook = Some((Call::Unlock, UNLOCK_FAILED));
+    assert_eq!(__erase_sector(0x0800_0000), UNLOCK_FAILED.get());
+    fixture::device().failing_hook = None;
+    assert_eq!(__erase_sector(0x0800_0000), 0);
+    assert_eq!(
+        fixture::device().calls[1..],
+        [Call::Unlock, Call::Unlock, Call::EraseSector(0x0800_0000)]
+    );
+}
+
+#[test]
+fn lock_is_skipped_when_unlock_never_succeeded() {
+    let _serial = traced();
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    fixture::device().failing_hook = Some((Call::Unlock, UNLOCK_FAILED));
+    assert_eq!(__erase_sector(0x0800_0000), UNLOCK_FAILED.get());
+    assert_eq!(__deinit(), 0);
+    assert_eq!(
+        fixture::device().calls[1..],
+        [Call::Unlock, Call::Uninit(Operation::Erase), Call::Deinit]
+    );
+}
+
+#[test]
+fn a_lock_error_is_reported_after_a_failed_request() {
+    let _serial = traced();
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_page(0x0800_0000, &[0; 0x10]), 0);
+    // Programming the same bytes again fails on the part.
+    assert_ne!(__program_page(0x0800_0000, &[0; 0x10]), 0);
+    fixture::device().failing_hook = Some((Call::Lock, LOCK_FAILED));
+    assert_eq!(__deinit(), LOCK_FAILED.get());
+    let device = fixture::device();
+    assert_eq!(device.drops, 1);
+    assert_eq!(
+        device.calls[device.calls.len() - 3..],
+        [Call::Lock, Call::Uninit(Operation::Program), Call::Deinit]
+    );
+}
+
+#[test]
+fn reinitializing_locks_the_previous_session_first() {
+    let _serial = traced();
+    assert_eq!(__init(0x0800_0000, 0, ERASE), 0);
+    assert_eq!(__erase_sector(0x0800_0000), 0);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(__program_page(0x0800_0000, &[0; 0x10]), 0);
+    assert_eq!(
+        fixture::device().calls[2..],
+        [
+            Call::EraseSector(0x0800_0000),
+            Call::Lock,
+            Call::Uninit(Operation::Erase),
+            Call::Deinit,
+            Call::Create(Operation::Program),
+            Call::Unlock,
+            Call::ProgramPage(0x0800_0000, 0x10),
+        ]
+    );
+}
@@ -0,0 +1,47 @@
+use flashops::error_codes::{INVALID_ARGUMENT, NOT_INITIALIZED};
+use flashops::{flash_algorithm, FlashOps, Operation, CONFIG_VOLATILE, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct Config;
+
+flash_algorithm!(Algo<Config>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+#[test]
+fn writes_reach_the_algorithm_once_unlocked() {
+    let _serial = fixture::start::<Algo<Config>>(__deinit);
+    fixture::device().trace_hooks = true;
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(unsafe { write_config(0x31, 0x02) }, 0);
+    assert_eq!(unsafe { write_config(CONFIG_VOLATILE | 0x01, 0x40) }, 0);
+    assert_eq!(
+        fixture::device().calls,
+        [
+            Call::Create(Operation::Program),
+            Call::Unlock,
+            Call::WriteConfig(0x31, 0x02),
+            Call::WriteConfig(0x8000_0001, 0x40),
+        ]
+    );
+}
+
+#[test]
+fn the_algorithm_error_is_returned() {
+    let _serial = fixture::start::<Algo<Config>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    assert_eq!(unsafe { write_config(0x05, 0) }, INVALID_ARGUMENT.get());
+    assert_eq!(fixture::device().calls[1..], [Call::WriteConfig(0x05, 0)]);
+}
+
+#[test]
+fn writes_need_an_initialized_algorithm() {
+    let _serial = fixture::start::<Algo<Config>>(__deinit);
+    assert_eq!(unsafe { write_config(0x01, 0) }, NOT_INITIALIZED.get());
+    assert!(fixture::device().calls.is_empty());
+}
@@ -0,0 +1,43 @@
+use flashops::error_codes::NOT_INITIALIZED;
+use flashops::{flash_algorithm, FlashOps, Operation, PROGRAM};
+
+use crate::fixture::{self, Algo, Call};
+
+pub struct ZeroLength;
+
+flash_algorithm!(Algo<ZeroLength>, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}]
+});
+
+#[test]
+fn zero_lengths_never_reach_the_algorithm() {
+    let _serial = fixture::start::<Algo<ZeroLength>>(__deinit);
+    assert_eq!(__init(0x0800_0000, 0, PROGRAM), 0);
+    // Out of range as well, which a non-empty request would be rejected for.
+    for address in [0x0800_0100, 0x0900_0000] {
+        assert_eq!(__program_page(address, &[]), 0);
+        assert_eq!(__program_pages(address, &[]), 0);
+        assert_eq!(__verify(address, &[]), 0);
+        assert_eq!(unsafe { erase_range(address, 0) }, 0);
+    }
+    assert_eq!(fixture::device().calls, [Call::Create(Operation::Program)]);
+    assert!(fixture::device()
+        .sim
+        .memory()
+        .iter()
+        .all(|&byte| byte == 0xFF));
+}
+
+#[test]
+fn zero_lengths_still_need_initialize() {
+    let _serial = fixture::start::<Algo<ZeroLength>>(__deinit);
+    let not_initialized = NOT_INITIALIZED.get();
+    assert_eq!(__program_page(0x0800_0100, &[]), not_initialized);
+    assert_eq!(__program_pages(0x0800_0100, &[]), not_initialized);
+    assert_eq!(__verify(0x0800_0100, &[]), not_initialized);
+    assert_eq!(unsafe { erase_range(0x0800_0100, 0) }, not_initialized);
+}
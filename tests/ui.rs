@@ -0,0 +1,12 @@
+//! Compile-time checks of `flash_algorithm!`, each case in `tests/ui` failing with the message
+//! recorded next to it.
+//!
+//! The cases are only checked, not built, so a `const` assertion only fails here when the
+//! expansion evaluates it, not when it sits in a generic function such as `program_chunked`.
+//! The feature conflicts rejected at the crate root depend on `flashops`'s own features and
+//! are covered by the fixture builds in `tests/fixtures.rs` instead.
+
+#[test]
+fn ui() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,12 @@
+include!("support/algo.rs");
+
+flash_algorithm!(Algo, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: [{size: 0x1000, address: 0x0}],
+    ecc_size: 8
+});
+
+fn main() {}
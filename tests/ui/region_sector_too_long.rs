@@ -0,0 +1,12 @@
+include!("support/algo.rs");
+
+flash_algorithm!(Algo, {
+    regions: [
+        {address: 0x0800_0000, size: 0x2000, sectors: [{size: 0x1000, address: 0x1800}]},
+        {address: 0x0810_0000, size: 0x800, sectors: [{size: 0x800, address: 0x0}]}
+    ],
+    page_size: 0x100,
+    empty_value: 0xFF
+});
+
+fn main() {}
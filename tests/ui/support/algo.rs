@@ -0,0 +1,40 @@
+// Algorithm shared by the UI tests through `include!`, implementing every method the
+// features of the test build require.
+
+use flashops::{flash_algorithm, Error, FlashOps, Operation};
+
+pub struct Algo;
+
+impl FlashOps for Algo {
+    fn create(_address: u32, _clock: u32, _operation: Operation) -> Result<Self, Error> {
+        Ok(Self)
+    }
+
+    fn erase_sector(&mut self, _address: u32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn program_page(&mut self, _address: u32, _data: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, _address: u32, _data: &mut [u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn blank_check(&mut self, _address: u32, _size: u32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn checksum(&mut self, _address: u32, _size: u32) -> Result<u32, Error> {
+        Ok(0)
+    }
+
+    fn read_device_id(&mut self) -> Result<u32, Error> {
+        Ok(0)
+    }
+
+    fn write_config(&mut self, _reg: u32, _value: u32) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,11 @@
+include!("support/algo.rs");
+
+flash_algorithm!(Algo, {
+    flash_address: 0x0800_0000,
+    flash_size: 0x4000,
+    page_size: 0x100,
+    empty_value: 0xFF,
+    sectors: uniform {size: 0x1000, count: 0}
+});
+
+fn main() {}